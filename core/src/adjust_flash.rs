@@ -0,0 +1,98 @@
+//! What adjust mode does with the partitions: starting the model, counting pulses while running,
+//! and carrying out the saves the model and the console ask for. Shared by the firmware and the
+//! simulator, which only differ in how they get events and draw.
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    adjust_model::{AdjustModel, Effect, Event, Settings, HISTORY_SHOWN},
+    audit::{recent_saves, save_logged, undo_last_save},
+    console::{self, Partitions},
+    counter::{read_and_increment_counter, read_counter},
+    datetime::datetime_from_counter,
+    event_log::{self, log_event, SetSource},
+    flash::FlashStorage,
+    profile::{read_timings, BootTiming},
+};
+
+/// Adjust mode as it starts after a boot, showing what is stored in `partitions`.
+pub fn start_model<F: FlashStorage>(
+    partitions: &mut Partitions<F>,
+    counting_edges: bool,
+) -> Result<AdjustModel, F::Error> {
+    let worst_boot = BootTiming::worst_case(&read_timings(&partitions.profile)?);
+    let time = datetime_from_counter(read_counter(&mut partitions.counter)?);
+    Ok(AdjustModel::new(
+        time,
+        counting_edges,
+        Settings::CONFIGURED,
+        worst_boot,
+        recent_saves(&partitions.saves, HISTORY_SHOWN)?,
+    ))
+}
+
+/// Count a pulse seen on the pulse input while running, and log it. Returns the event for the
+/// model.
+pub fn count_running_pulse<F: FlashStorage>(
+    partitions: &mut Partitions<F>,
+) -> Result<Event, F::Error> {
+    let value = read_and_increment_counter(&mut partitions.counter)?;
+    log_event(
+        &mut partitions.log,
+        &event_log::Event::Pulse { counter: value },
+    )?;
+    Ok(Event::Pulse {
+        clock: datetime_from_counter(value),
+    })
+}
+
+/// Carry out `effect` of the model on `partitions`. Returns the event reporting back to the
+/// model, if there is one. Effects on the display are left to the caller.
+pub fn apply_effect<F: FlashStorage>(
+    partitions: &mut Partitions<F>,
+    effect: Effect,
+) -> Result<Option<Event>, F::Error> {
+    let Partitions {
+        counter,
+        log,
+        saves,
+        ..
+    } = partitions;
+    let time = match effect {
+        Effect::Save { time, timing } => Some(save_logged(
+            counter,
+            log,
+            saves,
+            time,
+            timing,
+            SetSource::Buttons,
+        )?),
+        Effect::Undo => undo_last_save(counter, log, saves)?,
+        Effect::Redraw | Effect::FullRedraw | Effect::Sleep => None,
+    };
+    match time {
+        Some(time) => Ok(Some(stored(partitions, time)?)),
+        None => Ok(None),
+    }
+}
+
+/// The event for the model after a console command with `effect`.
+pub fn console_event<F: FlashStorage>(
+    partitions: &Partitions<F>,
+    effect: console::Effect,
+) -> Result<Event, F::Error> {
+    match effect {
+        console::Effect::Stored(time) => stored(partitions, time),
+        console::Effect::Redraw => Ok(Event::FullRedrawRequested),
+    }
+}
+
+fn stored<F: FlashStorage>(
+    partitions: &Partitions<F>,
+    time: NaiveDateTime,
+) -> Result<Event, F::Error> {
+    Ok(Event::Stored {
+        time,
+        history: recent_saves(&partitions.saves, HISTORY_SHOWN)?,
+    })
+}
@@ -0,0 +1,418 @@
+//! What adjust mode does in response to buttons, pulses and time passing, kept apart from the
+//! hardware so it can be driven from host tests.
+//!
+//! [`AdjustModel::handle`] takes an [`Event`] and returns the [`Effect`]s the caller has to carry
+//! out: flash writes, whose outcome comes back as another event, and redraws.
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{
+    config,
+    datetime::{clamp_datetime_to_counter, SaveTiming},
+    profile::BootTiming,
+    pulse::{counts_edges, PowerMode},
+};
+
+/// Time without input after an adjustment before the display is cleared of ghosting.
+pub const GHOST_CLEAR_MS: u32 = 5000;
+
+/// Number of past saves listed in adjust mode.
+pub const HISTORY_SHOWN: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Screen {
+    /// The clock face, while pulses are counted without rebooting.
+    Clock,
+    Adjust,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    Cycle,
+    Backward,
+    Forward,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Press(Button),
+    /// The button has been held down long enough to repeat.
+    Repeat(Button),
+    /// The button has been held down for a long press, after its press and some repeats.
+    LongPress(Button),
+    Tick {
+        elapsed_ms: u32,
+    },
+    /// A pulse was counted from the pulse input, advancing the counter to `clock`.
+    Pulse {
+        clock: NaiveDateTime,
+    },
+    /// The counter was set to `time`, from this model's effects or from the console. `history` is
+    /// the latest saves.
+    Stored {
+        time: NaiveDateTime,
+        history: Vec<(NaiveDateTime, NaiveDateTime)>,
+    },
+    /// Redraw the whole display, e.g. when asked to on the console.
+    FullRedrawRequested,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// Store `time` in the counter and report back with [`Event::Stored`].
+    Save {
+        time: NaiveDateTime,
+        timing: SaveTiming,
+    },
+    /// Undo the last save and report back with [`Event::Stored`] if there was one.
+    Undo,
+    /// Draw the model with a difference update.
+    Redraw,
+    /// Clear the display and draw the model.
+    FullRedraw,
+    /// Power down once the model is drawn, until a button is pressed. Never sent while counting
+    /// edges, as pulses would be missed.
+    Sleep,
+}
+
+/// How adjust mode behaves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settings {
+    pub power_mode: PowerMode,
+    /// Adjust mode is left after this long without a button press.
+    pub timeout_ms: u32,
+    /// Save unsaved changes when adjust mode times out, instead of discarding them.
+    pub save_on_timeout: bool,
+}
+
+impl Settings {
+    pub const CONFIGURED: Settings = Settings {
+        power_mode: config::POWER_MODE,
+        timeout_ms: config::ADJUST_TIMEOUT_MS,
+        save_on_timeout: config::SAVE_ON_ADJUST_TIMEOUT,
+    };
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdjustModel {
+    pub screen: Screen,
+    pub field: AdjustField,
+    pub time: NaiveDateTime,
+    /// Time held by the counter.
+    pub clock: NaiveDateTime,
+    pub changed: bool,
+    /// Pulses are counted from the pulse input rather than from power-ons.
+    pub counting_edges: bool,
+    /// Slowest recorded pulse boot phases, for tuning layouts to the time budget.
+    pub worst_boot: Option<BootTiming>,
+    /// The latest saves as the times before and after, oldest first.
+    pub history: Vec<(NaiveDateTime, NaiveDateTime)>,
+    settings: Settings,
+    /// Time since the last redraw, while a ghost clear may be due.
+    idle_ms: Option<u32>,
+    /// Time since the last button press on the adjust screen.
+    inactive_ms: u32,
+    /// Timed out, waiting for the save before leaving.
+    leaving: bool,
+}
+
+impl AdjustModel {
+    /// Start out with the counter at `clock`. While counting edges, the clock face is shown until a
+    /// button is pressed.
+    pub fn new(
+        clock: NaiveDateTime,
+        counting_edges: bool,
+        settings: Settings,
+        worst_boot: Option<BootTiming>,
+        history: Vec<(NaiveDateTime, NaiveDateTime)>,
+    ) -> AdjustModel {
+        AdjustModel {
+            screen: if counting_edges {
+                Screen::Clock
+            } else {
+                Screen::Adjust
+            },
+            field: AdjustField::Years,
+            time: clock,
+            clock,
+            changed: false,
+            counting_edges,
+            worst_boot,
+            history,
+            settings,
+            idle_ms: None,
+            inactive_ms: 0,
+            leaving: false,
+        }
+    }
+
+    pub fn save_timing(&self) -> SaveTiming {
+        if self.counting_edges {
+            SaveTiming::Now
+        } else {
+            SaveTiming::NextPulse
+        }
+    }
+
+    /// Time until the model needs an [`Event::Tick`], if it needs one at all.
+    pub fn next_tick_ms(&self) -> Option<u32> {
+        let ghost_clear_ms = self.idle_ms.map(|idle_ms| GHOST_CLEAR_MS - idle_ms);
+        let timeout_ms = self
+            .times_out()
+            .then(|| self.settings.timeout_ms.saturating_sub(self.inactive_ms));
+        [ghost_clear_ms, timeout_ms].into_iter().flatten().min()
+    }
+
+    pub fn handle(&mut self, event: Event) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        if matches!(
+            event,
+            Event::Press(_) | Event::Repeat(_) | Event::LongPress(_)
+        ) {
+            /* Pressing a button while a timed out change is saved stays in adjust mode. */
+            self.inactive_ms = 0;
+            self.leaving = false;
+        }
+        match event {
+            Event::Press(button) => self.press(button, &mut effects),
+            Event::Repeat(Button::Cycle) => {}
+            /* Holding the button must not save over and over. */
+            Event::Repeat(_) if self.on_action_field() => {}
+            Event::Repeat(button) => self.press(button, &mut effects),
+            /* Holding the field button skips to saving. */
+            Event::LongPress(Button::Cycle) if self.screen == Screen::Adjust => {
+                self.field = AdjustField::Store;
+                self.redraw(&mut effects);
+            }
+            Event::LongPress(_) => {}
+            Event::Tick { elapsed_ms } => {
+                if let Some(idle_ms) = self.idle_ms {
+                    let idle_ms = idle_ms.saturating_add(elapsed_ms);
+                    if idle_ms < GHOST_CLEAR_MS {
+                        self.idle_ms = Some(idle_ms);
+                    } else {
+                        self.idle_ms = None;
+                        if self.changed {
+                            effects.push(Effect::FullRedraw);
+                        }
+                    }
+                }
+                if self.times_out() {
+                    self.inactive_ms = self.inactive_ms.saturating_add(elapsed_ms);
+                    if self.inactive_ms >= self.settings.timeout_ms {
+                        self.time_out(&mut effects);
+                    }
+                }
+            }
+            Event::Pulse { clock } => {
+                self.clock = clock;
+                if !self.changed {
+                    self.time = clock;
+                }
+                if !self.counting_edges && counts_edges(self.settings.power_mode, true) {
+                    self.counting_edges = true;
+                    self.screen = Screen::Clock;
+                }
+                self.redraw(&mut effects);
+            }
+            Event::Stored { time, history } => {
+                self.time = time;
+                self.clock = time;
+                self.changed = false;
+                self.history = history;
+                if self.leaving {
+                    self.leave(&mut effects);
+                    return effects;
+                }
+                if self.counting_edges {
+                    self.screen = Screen::Clock;
+                }
+                self.redraw(&mut effects);
+            }
+            Event::FullRedrawRequested => {
+                self.idle_ms = None;
+                effects.push(Effect::FullRedraw);
+            }
+        }
+        effects
+    }
+
+    fn press(&mut self, button: Button, effects: &mut Vec<Effect>) {
+        /* The press that switches from the clock face does nothing else. */
+        if self.screen == Screen::Clock {
+            self.screen = Screen::Adjust;
+            if !self.changed {
+                self.time = self.clock;
+            }
+            self.redraw(effects);
+            return;
+        }
+        match button {
+            Button::Cycle => self.field = self.field.cycle(),
+            Button::Backward if self.on_action_field() => return,
+            Button::Forward if self.field == AdjustField::Store => {
+                effects.push(Effect::Save {
+                    time: self.time,
+                    timing: self.save_timing(),
+                });
+                return;
+            }
+            Button::Forward if self.field == AdjustField::Undo => {
+                effects.push(Effect::Undo);
+                return;
+            }
+            Button::Backward => {
+                self.time = adjust(self.field, AdjustDirection::Backward, self.time);
+                self.changed = true;
+            }
+            Button::Forward => {
+                self.time = adjust(self.field, AdjustDirection::Forward, self.time);
+                self.changed = true;
+            }
+        }
+        self.redraw(effects);
+    }
+
+    fn times_out(&self) -> bool {
+        self.screen == Screen::Adjust && !self.leaving
+    }
+
+    fn time_out(&mut self, effects: &mut Vec<Effect>) {
+        if self.changed && self.settings.save_on_timeout {
+            self.leaving = true;
+            effects.push(Effect::Save {
+                time: self.time,
+                timing: self.save_timing(),
+            });
+        } else {
+            self.time = self.clock;
+            self.changed = false;
+            self.leave(effects);
+        }
+    }
+
+    /// Go back to the clock face, and power down unless pulses are counted while running.
+    fn leave(&mut self, effects: &mut Vec<Effect>) {
+        self.leaving = false;
+        self.screen = Screen::Clock;
+        self.field = AdjustField::Years;
+        self.idle_ms = None;
+        effects.push(Effect::FullRedraw);
+        if !self.counting_edges {
+            effects.push(Effect::Sleep);
+        }
+    }
+
+    fn on_action_field(&self) -> bool {
+        matches!(self.field, AdjustField::Store | AdjustField::Undo)
+    }
+
+    fn redraw(&mut self, effects: &mut Vec<Effect>) {
+        self.idle_ms = Some(0);
+        effects.push(Effect::Redraw);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AdjustField {
+    Years,
+    Months,
+    Days,
+    Hours,
+    Minutes,
+    /// Only for pulse protocols with increments shorter than a minute.
+    Seconds,
+    Store,
+    /// Undo the last save.
+    Undo,
+}
+
+#[derive(Clone, Copy)]
+enum AdjustDirection {
+    Forward,
+    Backward,
+}
+
+impl AdjustField {
+    fn cycle(self) -> Self {
+        match self {
+            AdjustField::Years => AdjustField::Months,
+            AdjustField::Months => AdjustField::Days,
+            AdjustField::Days => AdjustField::Hours,
+            AdjustField::Hours => AdjustField::Minutes,
+            AdjustField::Minutes if seconds_adjustable() => AdjustField::Seconds,
+            AdjustField::Minutes => AdjustField::Store,
+            AdjustField::Seconds => AdjustField::Store,
+            AdjustField::Store => AdjustField::Undo,
+            AdjustField::Undo => AdjustField::Years,
+        }
+    }
+
+    pub fn format(self, datetime: NaiveDateTime) -> String {
+        let format_string = match self {
+            AdjustField::Years => "%Y",
+            AdjustField::Months => "%m",
+            AdjustField::Days => "%d",
+            AdjustField::Hours => "%H",
+            AdjustField::Minutes => "%M",
+            AdjustField::Seconds => "%S",
+            AdjustField::Store => "Save",
+            AdjustField::Undo => "Undo",
+        };
+        datetime.format(format_string).to_string()
+    }
+}
+
+pub fn seconds_adjustable() -> bool {
+    config::PULSE_PROTOCOL.increment < 60
+}
+
+/// Step `field` of `datetime`, by one unit or by one pulse for the seconds. The result is clamped to
+/// what the counter can represent.
+fn adjust(
+    field: AdjustField,
+    direction: AdjustDirection,
+    datetime: NaiveDateTime,
+) -> NaiveDateTime {
+    let mut date = datetime.date();
+    let mut time = datetime.time();
+    let mut overflow_days = 0;
+
+    let adjust_time = match direction {
+        AdjustDirection::Forward => NaiveTime::overflowing_add_signed,
+        AdjustDirection::Backward => NaiveTime::overflowing_sub_signed,
+    };
+    let adjust_date_duration = |date: NaiveDate, duration| {
+        match direction {
+            AdjustDirection::Forward => date.checked_add_signed(duration),
+            AdjustDirection::Backward => date.checked_sub_signed(duration),
+        }
+        .unwrap_or(date)
+    };
+    let adjust_date_months = |date: NaiveDate, months| {
+        match direction {
+            AdjustDirection::Forward => date.checked_add_months(months),
+            AdjustDirection::Backward => date.checked_sub_months(months),
+        }
+        .unwrap_or(date)
+    };
+
+    match field {
+        AdjustField::Seconds => {
+            let increment = config::PULSE_PROTOCOL.increment.into();
+            (time, overflow_days) = adjust_time(&time, chrono::Duration::seconds(increment))
+        }
+        AdjustField::Minutes => {
+            (time, overflow_days) = adjust_time(&time, chrono::Duration::minutes(1))
+        }
+        AdjustField::Hours => {
+            (time, overflow_days) = adjust_time(&time, chrono::Duration::hours(1))
+        }
+        AdjustField::Days => date = adjust_date_duration(date, chrono::Duration::days(1)),
+        AdjustField::Months => date = adjust_date_months(date, chrono::Months::new(1)),
+        AdjustField::Years => date = adjust_date_months(date, chrono::Months::new(12)),
+        AdjustField::Store | AdjustField::Undo => {}
+    }
+    date = adjust_date_duration(date, chrono::Duration::seconds(overflow_days));
+    clamp_datetime_to_counter(date.and_time(time))
+}
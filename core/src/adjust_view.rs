@@ -0,0 +1,75 @@
+//! Layout of the adjust screen.
+
+use crate::{
+    adjust_model::{seconds_adjustable, AdjustField, AdjustModel},
+    fb::{Framebuffer, Paint, Rect},
+};
+
+/// Draw the adjust screen for `model`.
+pub fn draw_adjust(framebuffer: &mut Framebuffer, model: &AdjustModel) {
+    const BUTTONS_START: i32 = 240;
+    const BUTTONS_SPACE: i32 = 69;
+    for (i, text) in [Some("RST"), None, Some("NEXT"), Some("-"), Some("+")]
+        .into_iter()
+        .enumerate()
+    {
+        if let Some(text) = text {
+            let pos = BUTTONS_START + i as i32 * BUTTONS_SPACE;
+            framebuffer.rect(
+                Paint::Darken,
+                Rect {
+                    x: pos - 20,
+                    y: 0,
+                    w: 40,
+                    h: 4,
+                },
+            );
+            framebuffer.text_centered(Paint::Darken, pos, 30, 30., text);
+        }
+    }
+    for (part, x, y) in [
+        (AdjustField::Hours, 400, 180),
+        (AdjustField::Minutes, 560, 180),
+        (AdjustField::Seconds, 720, 180),
+        (AdjustField::Days, 290, 300),
+        (AdjustField::Months, 450, 300),
+        (AdjustField::Years, 650, 300),
+        (AdjustField::Store, 380, 430),
+        (AdjustField::Undo, 660, 430),
+    ] {
+        if part == AdjustField::Seconds && !seconds_adjustable() {
+            continue;
+        }
+        framebuffer.text_centered(Paint::Darken, x, y, 94., &part.format(model.time));
+        if part == model.field {
+            framebuffer.rect(
+                Paint::Darken,
+                Rect {
+                    x: x - 40,
+                    y: y + 10,
+                    w: 80,
+                    h: 6,
+                },
+            );
+        }
+    }
+    if model.changed {
+        framebuffer.text_centered(Paint::Darken, 480, 480, 50., "not saved");
+    }
+    for (i, (old, new)) in model.history.iter().enumerate() {
+        let line = format!(
+            "{} -> {}",
+            old.format("%-d.%-m. %H:%M"),
+            new.format("%H:%M")
+        );
+        framebuffer.text_centered(Paint::Darken, 130, 400 + i as i32 * 30, 24., &line);
+    }
+    if let Some(worst) = model.worst_boot {
+        let summary = format!(
+            "slowest boot {} ms, draw {} ms",
+            worst.total_us / 1000,
+            worst.draw_us / 1000
+        );
+        framebuffer.text_centered(Paint::Darken, 480, 530, 24., &summary);
+    }
+}
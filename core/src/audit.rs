@@ -0,0 +1,213 @@
+//! History of manual time changes, and undoing the last one.
+//!
+//! Every save goes through [`save_logged`], which keeps a record of it with the counter value
+//! before the save in the `saves` partition, and writes a `Set` entry to the event log. The clock
+//! has no other source of time, so the history shows the time it was showing when the save
+//! happened. Every pulse boot writes to the event log, which wraps within days, so the saves are
+//! kept apart from it where only saves are written. The pulses counted since a save are told from
+//! the counter.
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    counter::{find_active_sector, read_counter, set_counter, set_counter_now},
+    datetime::{counter_from_datetime, datetime_from_counter, save_datetime, SaveTiming},
+    event_log::{log_event, Event, SetSource},
+    flash::FlashStorage,
+    ring,
+};
+
+const RECORD_SIZE: usize = 10;
+
+const FLAG_PENDING_BEFORE: u8 = 1 << 0;
+const FLAG_PENDING_AFTER: u8 = 1 << 1;
+
+/// A manual change of the counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Save {
+    pub old: u32,
+    pub new: u32,
+    pub source: SetSource,
+    pub pending_before: bool,
+    /// Whether the new value waited for its first pulse.
+    pub pending_after: bool,
+    /// Pulses counted after the save, until the next save or until now.
+    pub pulses_after: u32,
+}
+
+impl Save {
+    pub fn old_time(&self) -> NaiveDateTime {
+        datetime_from_counter(self.old)
+    }
+
+    pub fn new_time(&self) -> NaiveDateTime {
+        datetime_from_counter(self.new)
+    }
+
+    /// Layout: old value, new value, source, flags. Integers are big-endian. The pulses after
+    /// the save are not stored.
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..4].copy_from_slice(&self.old.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.new.to_be_bytes());
+        bytes[8] = self.source.code();
+        bytes[9] = [
+            (self.pending_before, FLAG_PENDING_BEFORE),
+            (self.pending_after, FLAG_PENDING_AFTER),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Save> {
+        if bytes.len() != RECORD_SIZE {
+            return None;
+        }
+        let get =
+            |position: usize| u32::from_be_bytes(bytes[position..position + 4].try_into().unwrap());
+        Some(Save {
+            old: get(0),
+            new: get(4),
+            source: SetSource::from_code(bytes[8]),
+            pending_before: bytes[9] & FLAG_PENDING_BEFORE != 0,
+            pending_after: bytes[9] & FLAG_PENDING_AFTER != 0,
+            pulses_after: 0,
+        })
+    }
+}
+
+/// Pulses the counter counted from holding `value`, waiting for its first pulse if `pending`, to
+/// holding `later`, waiting if `later_pending`.
+fn pulses_between(value: u32, pending: bool, later: u32, later_pending: bool) -> u32 {
+    /* A pending value absorbs the first pulse. */
+    later
+        .saturating_add(pending as u32)
+        .saturating_sub(value.saturating_add(later_pending as u32))
+}
+
+fn pending_set<F: FlashStorage>(counter: &F) -> Result<bool, F::Error> {
+    Ok(find_active_sector(counter)?.is_some_and(|active| active.pending_set))
+}
+
+/// The saves kept in `saves`, oldest first, with the pulses `counter` counted after each.
+pub fn history<F: FlashStorage>(saves: &F, counter: &mut F) -> Result<Vec<Save>, F::Error> {
+    let mut history = read_saves(saves)?;
+    let now = (read_counter(counter)?, pending_set(counter)?);
+    /* Each save lasted until the next one changed the counter again. */
+    let ends: Vec<(u32, bool)> = history
+        .iter()
+        .skip(1)
+        .map(|next| (next.old, next.pending_before))
+        .chain([now])
+        .collect();
+    for (save, (later, later_pending)) in history.iter_mut().zip(ends) {
+        save.pulses_after = pulses_between(save.new, save.pending_after, later, later_pending);
+    }
+    Ok(history)
+}
+
+fn read_saves<F: FlashStorage>(saves: &F) -> Result<Vec<Save>, F::Error> {
+    let records = ring::read_records(saves, RECORD_SIZE)?;
+    Ok(records
+        .iter()
+        .filter_map(|record| Save::decode(record))
+        .collect())
+}
+
+/// The latest `count` saves in `saves` as the times before and after, oldest first.
+pub fn recent_saves<F: FlashStorage>(
+    saves: &F,
+    count: usize,
+) -> Result<Vec<(NaiveDateTime, NaiveDateTime)>, F::Error> {
+    let records = ring::read_latest_records(saves, RECORD_SIZE, count)?;
+    Ok(records
+        .iter()
+        .filter_map(|record| Save::decode(record))
+        .map(|save| (save.old_time(), save.new_time()))
+        .collect())
+}
+
+/// What undoing `save` sets the counter to: the value before it, advanced by the pulses counted
+/// since. Returns the value and whether it should still wait for its first pulse.
+pub fn undo_target(save: &Save) -> (u32, bool) {
+    if save.pulses_after == 0 {
+        (save.old, save.pending_before)
+    } else {
+        /* Without the save, a pending value would have absorbed the first pulse. */
+        (
+            save.old.saturating_add(save.pulses_after) - save.pending_before as u32,
+            false,
+        )
+    }
+}
+
+/// Change the counter with `set`, which returns the new value, and keep and log the change with
+/// what is needed to undo it.
+fn set_logged<F: FlashStorage>(
+    counter: &mut F,
+    log: &mut F,
+    saves: &mut F,
+    source: SetSource,
+    set: impl FnOnce(&mut F) -> Result<u32, F::Error>,
+) -> Result<u32, F::Error> {
+    let old = read_counter(counter)?;
+    let pending_before = pending_set(counter)?;
+    let new = set(counter)?;
+    let save = Save {
+        old,
+        new,
+        source,
+        pending_before,
+        pending_after: pending_set(counter)?,
+        pulses_after: 0,
+    };
+    ring::append(saves, &save.encode())?;
+    let event = Event::Set {
+        old,
+        new,
+        source,
+        pending_before,
+    };
+    log_event(log, &event)?;
+    Ok(new)
+}
+
+/// Store `time` like [`save_datetime`], and keep and log the change.
+pub fn save_logged<F: FlashStorage>(
+    counter: &mut F,
+    log: &mut F,
+    saves: &mut F,
+    time: NaiveDateTime,
+    timing: SaveTiming,
+    source: SetSource,
+) -> Result<NaiveDateTime, F::Error> {
+    let new = set_logged(counter, log, saves, source, |counter| {
+        save_datetime(counter, time, timing).map(counter_from_datetime)
+    })?;
+    Ok(datetime_from_counter(new))
+}
+
+/// Undo the last save kept in `saves`. Undoing twice restores the undone save. Returns the time
+/// now stored, or `None` if nothing has been saved.
+pub fn undo_last_save<F: FlashStorage>(
+    counter: &mut F,
+    log: &mut F,
+    saves: &mut F,
+) -> Result<Option<NaiveDateTime>, F::Error> {
+    let last = match history(saves, counter)?.last() {
+        Some(&last) => last,
+        None => return Ok(None),
+    };
+    let (value, pending) = undo_target(&last);
+    set_logged(counter, log, saves, SetSource::Undo, |counter| {
+        if pending {
+            set_counter(counter, value)?;
+        } else {
+            set_counter_now(counter, value)?;
+        }
+        Ok(value)
+    })?;
+    Ok(Some(datetime_from_counter(value)))
+}
@@ -0,0 +1,68 @@
+//! Detecting bursts of catch-up pulses.
+//!
+//! After a power cut or a DST change, a master clock catches up by sending pulses much faster than
+//! usual. Those pulses are too short for a full render, so their boots show up in the event log as
+//! a `Boot` without a following `Drawn`. The power goes away between pulses, so the log is all that
+//! is left to tell a burst by. After [`crate::config::BURST_MIN_RAPID_BOOTS`] such boots in a row, the
+//! counter is still incremented but drawing waits until the burst has settled. After
+//! [`crate::config::BURST_MAX_HELD_BOOTS`] boots held back in a row, the next one draws right away
+//! anyway, so that boots which lost power for another reason can't keep the display from being
+//! drawn for good.
+
+use crate::{
+    event_log::{read_latest_events, Event},
+    flash::FlashStorage,
+};
+
+/// Number of pulse boots in a row at the end of `events` that lost power before the display was
+/// drawn.
+pub fn undrawn_pulse_boots(events: &[Event]) -> u32 {
+    /* Anything logged after a boot, a `Drawn` or what adjust mode logs, means it got that far. */
+    events
+        .iter()
+        .rev()
+        .take_while(|event| {
+            matches!(
+                event,
+                Event::Boot {
+                    pulse_boot: true,
+                    ..
+                }
+            )
+        })
+        .count() as u32
+}
+
+/// Number of boots in a row at the end of `events` that were held back for a burst and lost power
+/// before the display was drawn.
+pub fn held_back_boots(events: &[Event]) -> u32 {
+    events
+        .iter()
+        .rev()
+        .take_while(|event| {
+            matches!(
+                event,
+                Event::Boot {
+                    pulse_boot: true,
+                    catching_up: true,
+                    ..
+                }
+            )
+        })
+        .count() as u32
+}
+
+/// Whether a pulse boot logging to `log` is part of a catch-up burst, given how many undrawn boots
+/// in a row make a burst and how many boots in a row may be held back before one draws anyway.
+/// Called before the boot is logged.
+pub fn catching_up<F: FlashStorage>(
+    log: &F,
+    min_rapid_boots: u32,
+    max_held_boots: u32,
+) -> Result<bool, F::Error> {
+    let events = read_latest_events(log, min_rapid_boots.max(max_held_boots) as usize)?;
+    Ok(
+        undrawn_pulse_boots(&events) >= min_rapid_boots
+            && held_back_boots(&events) < max_held_boots,
+    )
+}
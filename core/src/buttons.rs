@@ -0,0 +1,235 @@
+//! Turning the levels of the buttons into clicks, long presses, double clicks and repeats.
+//!
+//! [`Buttons::poll`] is given the time of each reading, so the timing can be tested without
+//! waiting. Buttons are pressed when their pin reads low, and are tracked independently of each
+//! other.
+
+use embedded_hal::digital::v2::InputPin;
+
+/// Timing of button events, in milliseconds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ButtonTiming {
+    /// How long a level has to hold before it is believed.
+    pub debounce_ms: u64,
+    /// Holding a button this long is a long press rather than a click.
+    pub long_press_ms: u64,
+    /// Time from releasing a click to the second press of a double click.
+    pub double_click_ms: u64,
+    /// Holding a button this long starts repeating.
+    pub repeat_delay_ms: u64,
+    /// Time to the first repeat after the delay. Each repeat comes a quarter sooner than the one
+    /// before, down to `repeat_min_interval_ms`.
+    pub repeat_interval_ms: u64,
+    pub repeat_min_interval_ms: u64,
+}
+
+impl ButtonTiming {
+    pub const DEFAULT: ButtonTiming = ButtonTiming {
+        debounce_ms: 20,
+        long_press_ms: 800,
+        double_click_ms: 300,
+        repeat_delay_ms: 500,
+        repeat_interval_ms: 200,
+        repeat_min_interval_ms: 40,
+    };
+}
+
+/// What happened to button `B`.
+///
+/// A held button gives both repeats and a long press; each use of a button picks the events it
+/// needs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonEvent<B> {
+    /// The button went down. Sent right away, before it is known what the press turns into.
+    Down(B),
+    /// The button was pressed and released once. Sent when it is clear that no double click
+    /// follows.
+    Click(B),
+    DoubleClick(B),
+    /// The button has been held for the long press time.
+    LongPress(B),
+    /// The button is still held.
+    Repeat(B),
+    /// The second button went down while the first was held. Neither gives any more events until
+    /// both are released.
+    Chord(B, B),
+}
+
+struct Tracker<B, P> {
+    button: B,
+    pin: P,
+    /// Last level read, and since when.
+    raw: bool,
+    raw_since_ms: u64,
+    /// Debounced level.
+    pressed: bool,
+    pressed_at_ms: u64,
+    long_press_sent: bool,
+    next_repeat_ms: u64,
+    repeat_interval_ms: u64,
+    /// Release time of a click that may still become a double click.
+    click_released_ms: Option<u64>,
+    /// This press is the second one of a double click.
+    second_press: bool,
+    /// This press is part of a chord.
+    in_chord: bool,
+}
+
+pub struct Buttons<B, P> {
+    timing: ButtonTiming,
+    trackers: Vec<Tracker<B, P>>,
+}
+
+impl<B: Copy, P: InputPin> Buttons<B, P> {
+    /// Track the `buttons`, which are all taken to be released at first.
+    pub fn new(timing: ButtonTiming, buttons: Vec<(B, P)>) -> Buttons<B, P> {
+        let trackers = buttons
+            .into_iter()
+            .map(|(button, pin)| Tracker {
+                button,
+                pin,
+                raw: false,
+                raw_since_ms: 0,
+                pressed: false,
+                pressed_at_ms: 0,
+                long_press_sent: false,
+                next_repeat_ms: 0,
+                repeat_interval_ms: 0,
+                click_released_ms: None,
+                second_press: false,
+                in_chord: false,
+            })
+            .collect();
+        Buttons { timing, trackers }
+    }
+
+    /// Read the pins at `now_ms` and return the events since the previous poll.
+    pub fn poll(&mut self, now_ms: u64) -> Result<Vec<ButtonEvent<B>>, P::Error> {
+        let timing = self.timing;
+        let mut events = Vec::new();
+        for index in 0..self.trackers.len() {
+            let tracker = &mut self.trackers[index];
+            let raw = tracker.pin.is_low()?;
+            if raw != tracker.raw {
+                tracker.raw = raw;
+                tracker.raw_since_ms = now_ms;
+            }
+            let settled = now_ms - tracker.raw_since_ms >= timing.debounce_ms;
+            if settled && tracker.raw != tracker.pressed {
+                if tracker.raw {
+                    self.press(index, &mut events);
+                } else {
+                    self.release(index, &mut events);
+                }
+            }
+
+            let tracker = &mut self.trackers[index];
+            /* A release that is still settling stops the repeats already. */
+            if tracker.pressed && tracker.raw && !tracker.in_chord {
+                let held_ms = now_ms - tracker.pressed_at_ms;
+                if held_ms >= timing.long_press_ms && !tracker.long_press_sent {
+                    tracker.long_press_sent = true;
+                    events.push(ButtonEvent::LongPress(tracker.button));
+                }
+                if now_ms >= tracker.next_repeat_ms {
+                    events.push(ButtonEvent::Repeat(tracker.button));
+                    tracker.next_repeat_ms += tracker.repeat_interval_ms;
+                    tracker.repeat_interval_ms =
+                        (tracker.repeat_interval_ms * 3 / 4).max(timing.repeat_min_interval_ms);
+                }
+            }
+            if let Some(released_ms) = tracker.click_released_ms {
+                if now_ms - released_ms >= timing.double_click_ms {
+                    tracker.click_released_ms = None;
+                    events.push(ButtonEvent::Click(tracker.button));
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// When the next event is due if the levels stay as they are, i.e. the latest time to poll
+    /// again.
+    pub fn next_deadline_ms(&self) -> Option<u64> {
+        let timing = self.timing;
+        self.trackers
+            .iter()
+            .flat_map(|tracker| {
+                let settling = tracker.raw != tracker.pressed;
+                let held = tracker.pressed && tracker.raw && !tracker.in_chord;
+                [
+                    settling.then_some(tracker.raw_since_ms + timing.debounce_ms),
+                    (held && !tracker.long_press_sent)
+                        .then_some(tracker.pressed_at_ms + timing.long_press_ms),
+                    held.then_some(tracker.next_repeat_ms),
+                    tracker
+                        .click_released_ms
+                        .map(|released_ms| released_ms + timing.double_click_ms),
+                ]
+            })
+            .flatten()
+            .min()
+    }
+
+    /// Whether any button is down or an event is still to come from an earlier press.
+    pub fn busy(&self) -> bool {
+        self.trackers
+            .iter()
+            .any(|tracker| tracker.raw || tracker.pressed || tracker.click_released_ms.is_some())
+    }
+
+    fn press(&mut self, index: usize, events: &mut Vec<ButtonEvent<B>>) {
+        let timing = self.timing;
+        let held = self
+            .trackers
+            .iter()
+            .position(|other| other.pressed && !other.in_chord);
+        let tracker = &mut self.trackers[index];
+        let pressed_at_ms = tracker.raw_since_ms;
+        tracker.pressed = true;
+        tracker.pressed_at_ms = pressed_at_ms;
+        tracker.long_press_sent = false;
+        tracker.next_repeat_ms = pressed_at_ms + timing.repeat_delay_ms;
+        tracker.repeat_interval_ms = timing.repeat_interval_ms;
+        tracker.second_press = tracker.click_released_ms.take().is_some();
+        let button = tracker.button;
+        match held {
+            Some(other) => {
+                let other = &mut self.trackers[other];
+                other.in_chord = true;
+                other.click_released_ms = None;
+                events.push(ButtonEvent::Chord(other.button, button));
+                self.trackers[index].in_chord = true;
+            }
+            None => events.push(ButtonEvent::Down(button)),
+        }
+    }
+
+    fn release(&mut self, index: usize, events: &mut Vec<ButtonEvent<B>>) {
+        let timing = self.timing;
+        let tracker = &mut self.trackers[index];
+        tracker.pressed = false;
+        if tracker.in_chord {
+            /* The chord ends when the last of its buttons is released. */
+            if !self
+                .trackers
+                .iter()
+                .any(|other| other.in_chord && other.pressed)
+            {
+                for other in &mut self.trackers {
+                    other.in_chord = false;
+                }
+            }
+            return;
+        }
+        let held_ms = tracker.raw_since_ms - tracker.pressed_at_ms;
+        if held_ms >= timing.long_press_ms {
+            return;
+        }
+        if tracker.second_press {
+            events.push(ButtonEvent::DoubleClick(tracker.button));
+        } else {
+            tracker.click_released_ms = Some(tracker.raw_since_ms);
+        }
+    }
+}
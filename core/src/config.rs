@@ -0,0 +1,49 @@
+//! Build-time configuration for the installation.
+
+use crate::{datetime::PulseProtocol, pulse::PowerMode};
+
+/// GPIO that reads high when the board is powered from USB instead of the pulse line, or `None`
+/// if the board has no such connection. The LilyGo T5 4.7" does not have one out of the box.
+/// Without it, plugging in USB and resets by espflash or the reset button add a minute, as they
+/// look like a power-on to the ESP32.
+pub const VBUS_SENSE_GPIO: Option<i32> = None;
+
+/// Number of pulse boots in a row that lost power before drawing, after which the next ones are
+/// taken as catch-up pulses and drawing is held back. More than one, so that a single boot that
+/// lost power for another reason doesn't hold back the next pulse.
+pub const BURST_MIN_RAPID_BOOTS: u32 = 3;
+
+/// Number of boots in a row that may be held back for a burst and lose power before the next one
+/// draws right away anyway.
+pub const BURST_MAX_HELD_BOOTS: u32 = 5;
+
+/// During a burst, the display is only drawn if the board is still powered this long after the
+/// boot, which means the burst is over. The quick clear and draw, about a second, have to fit in
+/// a normal pulse of about two seconds after it, or the held back boots would never draw.
+pub const BURST_SETTLE_MS: u64 = 700;
+
+/// GPIO connected to the pulse line through an optocoupler, reading high for one polarity and low
+/// for the other, or `None` to not check for missed pulses.
+pub const POLARITY_SENSE_GPIO: Option<i32> = None;
+
+/// What one pulse on the line means. Changing this reinterprets the stored counter, so set the
+/// time again afterwards.
+pub const PULSE_PROTOCOL: PulseProtocol = PulseProtocol::MINUTE;
+
+/// Whether the board is powered by the pulses or permanently. `Auto` starts out pulse-powered and
+/// switches over when `PULSE_INPUT_GPIO` sees a pulse while running.
+pub const POWER_MODE: PowerMode = PowerMode::Auto;
+
+/// GPIO reading high while a pulse is on the line, for counting pulses without rebooting. `None`
+/// limits the clock to counting power-ons.
+pub const PULSE_INPUT_GPIO: Option<i32> = None;
+
+/// Shorter activity on the pulse input is noise.
+pub const PULSE_MIN_LENGTH_US: i64 = 50_000;
+
+/// Adjust mode goes back to the clock face after this long without a button press, and powers
+/// down unless it counts pulses from `PULSE_INPUT_GPIO`.
+pub const ADJUST_TIMEOUT_MS: u32 = 120_000;
+
+/// Whether a change that has not been saved when adjust mode times out is saved or discarded.
+pub const SAVE_ON_ADJUST_TIMEOUT: bool = false;
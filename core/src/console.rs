@@ -0,0 +1,264 @@
+//! Line-based command interpreter for the serial console.
+//!
+//! Commands:
+//! - `get`: print the stored time
+//! - `set 2026-10-17T12:34`: store a new time
+//! - `add -3`: move the stored time by a number of minutes
+//! - `status`: print the raw counter state
+//! - `redraw`: fully redraw the display
+//! - `timing`: print the latest boot timings and the worst case
+//! - `log`: print the latest entries of the event log
+//! - `history`: print the manual time changes
+//! - `undo`: undo the last time change
+
+use std::fmt;
+
+use chrono::{NaiveDateTime, TimeDelta};
+
+use crate::{
+    audit::{history, save_logged, undo_last_save},
+    counter::{erase_counts, find_active_sector, read_counter},
+    datetime::{datetime_from_counter, SaveTiming},
+    event_log::{read_events, Event, SetSource},
+    flash::FlashStorage,
+    profile::{read_timings, BootTiming},
+};
+
+pub const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// Number of boots listed by `timing`.
+const TIMINGS_SHOWN: usize = 10;
+
+/// Number of events listed by `log`.
+const EVENTS_SHOWN: usize = 30;
+
+/// Largest number of minutes `add` accepts either way, more than the counter can span.
+const ADD_MAX_MINUTES: u64 = u32::MAX as u64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Get,
+    Set(NaiveDateTime),
+    Add(i64),
+    Status,
+    Redraw,
+    Timing,
+    Log,
+    History,
+    Undo,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument,
+    BadArgument(String),
+    TooManyArguments,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty command"),
+            ParseError::UnknownCommand(name) => write!(f, "unknown command {name}"),
+            ParseError::MissingArgument => write!(f, "missing argument"),
+            ParseError::BadArgument(argument) => write!(f, "bad argument {argument}"),
+            ParseError::TooManyArguments => write!(f, "too many arguments"),
+        }
+    }
+}
+
+pub fn parse(line: &str) -> Result<Command, ParseError> {
+    let mut words = line.split_whitespace();
+    let name = words.next().ok_or(ParseError::Empty)?;
+    let mut argument = || words.next().ok_or(ParseError::MissingArgument);
+    let command = match name {
+        "get" => Command::Get,
+        "set" => {
+            let argument = argument()?;
+            NaiveDateTime::parse_from_str(argument, DATETIME_FORMAT)
+                .map(Command::Set)
+                .map_err(|_| ParseError::BadArgument(argument.to_string()))?
+        }
+        "add" => {
+            let argument = argument()?;
+            argument
+                .parse()
+                .ok()
+                .filter(|minutes: &i64| minutes.unsigned_abs() <= ADD_MAX_MINUTES)
+                .map(Command::Add)
+                .ok_or_else(|| ParseError::BadArgument(argument.to_string()))?
+        }
+        "status" => Command::Status,
+        "redraw" => Command::Redraw,
+        "timing" => Command::Timing,
+        "log" => Command::Log,
+        "history" => Command::History,
+        "undo" => Command::Undo,
+        _ => return Err(ParseError::UnknownCommand(name.to_string())),
+    };
+    if words.next().is_some() {
+        return Err(ParseError::TooManyArguments);
+    }
+    Ok(command)
+}
+
+/// Side effect the caller has to carry out after a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// A new time was stored, anything showing the old one should be updated.
+    Stored(NaiveDateTime),
+    Redraw,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub reply: String,
+    pub effect: Option<Effect>,
+}
+
+/// The partitions commands work on.
+pub struct Partitions<F> {
+    pub counter: F,
+    pub profile: F,
+    pub log: F,
+    pub saves: F,
+}
+
+/// Run `command`. Stored times are shown according to `save_timing`.
+pub fn execute<F: FlashStorage>(
+    partitions: &mut Partitions<F>,
+    command: Command,
+    save_timing: SaveTiming,
+) -> Result<Outcome, F::Error> {
+    let flash = &mut partitions.counter;
+    let outcome = match command {
+        Command::Get => {
+            let time = datetime_from_counter(read_counter(flash)?);
+            Outcome {
+                reply: time.format(DATETIME_FORMAT).to_string(),
+                effect: None,
+            }
+        }
+        Command::Set(time) => store(partitions, time, save_timing)?,
+        Command::Add(minutes) => {
+            let time = datetime_from_counter(read_counter(flash)?);
+            let time = TimeDelta::try_minutes(minutes)
+                .and_then(|delta| time.checked_add_signed(delta))
+                .unwrap_or(time);
+            store(partitions, time, save_timing)?
+        }
+        Command::Status => {
+            let value = read_counter(flash)?;
+            let mut reply = format!("counter {value}");
+            if let Some(active) = find_active_sector(flash)? {
+                let erase_count = erase_counts(flash)?[active.index as usize].unwrap_or(0);
+                reply += &format!(
+                    ", base {}, offset {}, sector {}, erase count {erase_count}, \
+                     corrected missed pulses {}",
+                    active.header.base,
+                    active.offset,
+                    active.index,
+                    active.corrections()
+                );
+                if let Some(corruption) = active.corruption {
+                    reply += &format!(", corrupted: {corruption:?}");
+                }
+            }
+            Outcome {
+                reply,
+                effect: None,
+            }
+        }
+        Command::Redraw => Outcome {
+            reply: "ok".to_string(),
+            effect: Some(Effect::Redraw),
+        },
+        Command::Timing => {
+            let timings = read_timings(&partitions.profile)?;
+            let latest = &timings[timings.len().saturating_sub(TIMINGS_SHOWN)..];
+            let mut lines: Vec<String> = latest.iter().map(BootTiming::to_string).collect();
+            match BootTiming::worst_case(&timings) {
+                Some(worst) => lines.push(format!("worst of {}: {worst}", timings.len())),
+                None => lines.push("no boots recorded".to_string()),
+            }
+            Outcome {
+                reply: lines.join("\n"),
+                effect: None,
+            }
+        }
+        Command::Log => {
+            let events = read_events(&partitions.log)?;
+            let latest = &events[events.len().saturating_sub(EVENTS_SHOWN)..];
+            let reply = if latest.is_empty() {
+                "log is empty".to_string()
+            } else {
+                let lines: Vec<String> = latest.iter().map(Event::to_string).collect();
+                lines.join("\n")
+            };
+            Outcome {
+                reply,
+                effect: None,
+            }
+        }
+        Command::History => {
+            let saves = history(&partitions.saves, &mut partitions.counter)?;
+            let reply = if saves.is_empty() {
+                "nothing saved".to_string()
+            } else {
+                let lines: Vec<String> = saves
+                    .iter()
+                    .map(|save| {
+                        format!(
+                            "{} -> {} ({:?}), {} pulses since",
+                            save.old_time().format(DATETIME_FORMAT),
+                            save.new_time().format(DATETIME_FORMAT),
+                            save.source,
+                            save.pulses_after
+                        )
+                    })
+                    .collect();
+                lines.join("\n")
+            };
+            Outcome {
+                reply,
+                effect: None,
+            }
+        }
+        Command::Undo => match undo_last_save(
+            &mut partitions.counter,
+            &mut partitions.log,
+            &mut partitions.saves,
+        )? {
+            Some(stored) => Outcome {
+                reply: format!("restored {}", stored.format(DATETIME_FORMAT)),
+                effect: Some(Effect::Stored(stored)),
+            },
+            None => Outcome {
+                reply: "nothing to undo".to_string(),
+                effect: None,
+            },
+        },
+    };
+    Ok(outcome)
+}
+
+fn store<F: FlashStorage>(
+    partitions: &mut Partitions<F>,
+    time: NaiveDateTime,
+    timing: SaveTiming,
+) -> Result<Outcome, F::Error> {
+    let stored = save_logged(
+        &mut partitions.counter,
+        &mut partitions.log,
+        &mut partitions.saves,
+        time,
+        timing,
+        SetSource::Console,
+    )?;
+    Ok(Outcome {
+        reply: format!("stored {}", stored.format(DATETIME_FORMAT)),
+        effect: Some(Effect::Stored(stored)),
+    })
+}
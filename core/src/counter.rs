@@ -0,0 +1,508 @@
+//! Monotonically increasing counter stored in flash.
+//!
+//! The counter partition is used as a ring of flash sectors. A sector starts with a checksummed
+//! header holding the base value, followed by a unary offset region where every increment flips
+//! one bit from 1 to 0. When the offset region is full, or when the counter is set to a new value,
+//! the *next* sector in the ring is erased and given a new header with a higher sequence number.
+//! The sector with the highest sequence number and a valid header wins, so losing power at any
+//! point leaves either the old or the new value readable, and erases are spread evenly over the
+//! whole partition.
+//!
+//! A sector started by setting the counter carries a pending-set flag. The first increment after
+//! that only clears a marker byte instead of advancing the value, so the first pulse after a set
+//! shows exactly the time that was set.
+//!
+//! The marker bytes also hold the polarity phase of the pulse line, which is carried over to new
+//! sectors, and a unary count of missed pulses corrected in this sector. Earlier corrections are
+//! summed up in the header.
+
+use crate::{
+    crc::crc32,
+    flash::{FlashStorage, SECTOR_SIZE},
+    polarity::{self, PolarityCheck},
+};
+
+const MAGIC: [u8; 4] = *b"PSLC";
+const VERSION: u8 = 1;
+
+const HEADER_SIZE: usize = 32;
+/// Bytes between the header and the offset region are reserved for write-once markers and left
+/// erased.
+const OFFSET_REGION_START: u32 = 64;
+/// Cleared when the first increment after a set has been absorbed.
+const PENDING_SET_MARKER: u32 = HEADER_SIZE as u32;
+/// Polarity phase of the pulse line, see [`crate::polarity`].
+const POLARITY_MARKER: u32 = PENDING_SET_MARKER + 1;
+const POLARITY_PHASE_CLEAR: u8 = 0xF0;
+const POLARITY_PHASE_SET: u8 = 0x0F;
+/// Unary count of missed pulses corrected in this sector.
+const CORRECTIONS_START: u32 = HEADER_SIZE as u32 + 8;
+
+const FLAG_PENDING_SET: u8 = 1 << 0;
+
+/// Number of increments the offset region of a sector holds before the next sector is started.
+pub const OFFSET_CAPACITY: u32 = (SECTOR_SIZE - OFFSET_REGION_START) * 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub sequence: u32,
+    pub base: u32,
+    /// How many times this sector has been erased, including the erase before this header.
+    pub erase_count: u32,
+    /// The sector was started by setting the counter, so the next increment is absorbed.
+    pub pending_set: bool,
+    /// Missed pulses corrected before this sector was started.
+    pub corrections: u32,
+}
+
+impl Header {
+    /// Layout: magic, version, flags, 2 reserved bytes, sequence, base, erase count, corrections,
+    /// reserved up to the CRC-32 of everything before it in the last 4 bytes. Integers are
+    /// big-endian.
+    fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0xFF; HEADER_SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = if self.pending_set { FLAG_PENDING_SET } else { 0 };
+        bytes[8..12].copy_from_slice(&self.sequence.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.base.to_be_bytes());
+        bytes[16..20].copy_from_slice(&self.erase_count.to_be_bytes());
+        bytes[20..24].copy_from_slice(&self.corrections.to_be_bytes());
+        let crc = crc32(&bytes[..HEADER_SIZE - 4]);
+        bytes[HEADER_SIZE - 4..].copy_from_slice(&crc.to_be_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Header> {
+        let bytes: &[u8; HEADER_SIZE] = bytes.get(..HEADER_SIZE)?.try_into().ok()?;
+        let crc = u32::from_be_bytes(bytes[HEADER_SIZE - 4..].try_into().unwrap());
+        if bytes[0..4] != MAGIC || bytes[4] != VERSION || crc32(&bytes[..HEADER_SIZE - 4]) != crc {
+            return None;
+        }
+        let get =
+            |position: usize| u32::from_be_bytes(bytes[position..position + 4].try_into().unwrap());
+        Some(Header {
+            sequence: get(8),
+            base: get(12),
+            erase_count: get(16),
+            pending_set: bytes[5] & FLAG_PENDING_SET != 0,
+            corrections: get(20),
+        })
+    }
+}
+
+/// The sector currently holding the counter.
+#[derive(Clone, Copy, Debug)]
+pub struct ActiveSector {
+    pub index: u32,
+    pub header: Header,
+    /// Number of bits flipped in the offset region.
+    pub offset: u32,
+    /// Set if the offset region is not a valid unary counter. `offset` is then the safest reading.
+    pub corruption: Option<Corruption>,
+    /// The counter was set and no increment has been absorbed yet.
+    pub pending_set: bool,
+    /// Pulse line polarity phase, once learned.
+    pub polarity_phase: Option<bool>,
+    /// Missed pulses corrected in this sector.
+    corrections_in_sector: u32,
+    /// Byte index of the first not fully flipped byte in the offset region.
+    head_index: u32,
+    head_byte: Option<u8>,
+}
+
+/// Ways the offset region can deviate from a run of zero bytes, one partially flipped byte and
+/// then erased bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Corruption {
+    /// Bits cleared after the head byte, e.g. by a bit flip. `index` is the byte offset of the
+    /// first one from the start of the offset region.
+    StrayBits { index: u32 },
+    /// The head byte does not have its cleared bits contiguous from the most significant end.
+    NonMonotonicHead { index: u32, byte: u8 },
+    /// Bits set again in a byte before the head, which was cleared already. `index` is the byte
+    /// offset of that byte.
+    SetBits { index: u32 },
+}
+
+/// Decode a unary offset region, checking its shape.
+///
+/// Increments only ever clear bits of the head byte, so the value is read up to and including
+/// it. Every cleared bit of the head byte counts, which keeps a damaged head from going backwards
+/// and moves it ahead by 7 at most.
+///
+/// A single flipped bit can't move the value far either way. Stray bits after the head are
+/// reported but not counted: a flipped bit near the end of the sector would otherwise move the
+/// clock ahead by days. A bit set again before the head shows up as a partial byte followed by
+/// fully cleared ones, which no flip can produce, so the value is read from the end of that run.
+/// Without any fully cleared byte in between, the value is only read from the later byte if the
+/// earlier one could not have been a head, which limits the move to two bytes.
+pub fn check_offset_region(region: &[u8]) -> (u32, Option<Corruption>) {
+    let head_index = match region.iter().position(|&x| x != 0) {
+        Some(head_index) => head_index,
+        None => return (region.len() as u32 * 8, None),
+    };
+    let read_at = |index: usize| index as u32 * 8 + region[index].count_zeros();
+    let monotonic = |byte: u8| byte.leading_zeros() + byte.trailing_ones() == 8;
+
+    /* The last byte with cleared bits, which is the head unless the region is damaged. */
+    let last = match region.iter().rposition(|&x| x != 0xFF) {
+        Some(last) if last > head_index => last,
+        _ => {
+            let head_byte = region[head_index];
+            let corruption = (!monotonic(head_byte)).then_some(Corruption::NonMonotonicHead {
+                index: head_index as u32,
+                byte: head_byte,
+            });
+            return (read_at(head_index), corruption);
+        }
+    };
+    let between = &region[head_index + 1..last];
+    let cleared_run =
+        between.iter().all(|&x| x == 0) && (!between.is_empty() || !monotonic(region[head_index]));
+    if cleared_run && monotonic(region[last]) {
+        let corruption = Corruption::SetBits {
+            index: head_index as u32,
+        };
+        return (read_at(last), Some(corruption));
+    }
+    let stray = region[head_index + 1..]
+        .iter()
+        .position(|&x| x != 0xFF)
+        .unwrap_or(0);
+    let corruption = Corruption::StrayBits {
+        index: (head_index + 1 + stray) as u32,
+    };
+    (read_at(head_index), Some(corruption))
+}
+
+impl ActiveSector {
+    pub fn value(&self) -> u32 {
+        self.header.base.saturating_add(self.offset)
+    }
+
+    /// Total number of missed pulses corrected.
+    pub fn corrections(&self) -> u32 {
+        self.header.corrections + self.corrections_in_sector
+    }
+}
+
+pub fn read_counter<F: FlashStorage>(flash: &mut F) -> Result<u32, F::Error> {
+    read_and_increment_counter_impl(flash, false)
+}
+
+pub fn read_and_increment_counter<F: FlashStorage>(flash: &mut F) -> Result<u32, F::Error> {
+    read_and_increment_counter_impl(flash, true)
+}
+
+/// Count the pulse that caused this boot. If the polarity `high` of the pulse is known, it is
+/// compared to the stored phase, and a pulse missed before this one is counted too. Returns the
+/// value to show and whether a missed pulse was corrected.
+pub fn count_pulse<F: FlashStorage>(
+    flash: &mut F,
+    polarity: Option<bool>,
+) -> Result<(u32, bool), F::Error> {
+    let value = read_and_increment_counter(flash)?;
+    let high = match polarity {
+        Some(high) => high,
+        None => return Ok((value, false)),
+    };
+    let phase = find_active_sector(flash)?.and_then(|active| active.polarity_phase);
+    match polarity::check(phase, value, high) {
+        PolarityCheck::Learned(phase) => {
+            set_polarity_phase(flash, phase)?;
+            Ok((value, false))
+        }
+        PolarityCheck::Matched => Ok((value, false)),
+        PolarityCheck::Missed => {
+            record_correction(flash)?;
+            Ok((read_and_increment_counter(flash)?, true))
+        }
+    }
+}
+
+/// Monotonically increasing counter implementation which minimizes the use of the expensive flash
+/// erase operation.
+fn read_and_increment_counter_impl<F: FlashStorage>(
+    flash: &mut F,
+    increment: bool,
+) -> Result<u32, F::Error> {
+    let active = match find_active_sector(flash)? {
+        Some(active) => active,
+        /* Nothing valid stored yet. Start from the legacy single-sector counter if there is one. */
+        None => {
+            let value = read_legacy_counter(flash)?;
+            if increment {
+                let value = value.saturating_add(1);
+                start_sector(flash, None, value, false, None)?;
+                return Ok(value);
+            }
+            return Ok(value);
+        }
+    };
+
+    if increment {
+        match active.head_byte {
+            /* First pulse after a set, which shows the value as it was set. */
+            _ if active.pending_set => {
+                let marker = sector_start(active.index) + PENDING_SET_MARKER;
+                flash.write(marker, &[0x00])?;
+                return Ok(active.value());
+            }
+            /* A corrupted offset region can't be continued, move on to a clean sector. */
+            _ if active.corruption.is_some() => {
+                start_sector(
+                    flash,
+                    Some(&active),
+                    active.value().saturating_add(1),
+                    false,
+                    active.polarity_phase,
+                )?;
+            }
+            /* Increase the offset unary counter by one bit. */
+            Some(head_byte) => {
+                let new_head_byte = head_byte >> 1;
+                let byte_index =
+                    sector_start(active.index) + OFFSET_REGION_START + active.head_index;
+                flash.write(byte_index, &[new_head_byte])?;
+            }
+            /* The offset unary counter is already full, continue in the next sector. */
+            None => {
+                start_sector(
+                    flash,
+                    Some(&active),
+                    active.value().saturating_add(1),
+                    false,
+                    active.polarity_phase,
+                )?;
+            }
+        }
+        return Ok(active.value().saturating_add(1));
+    }
+
+    Ok(active.value())
+}
+
+/// Overwrite counter. Costs one sector erase, so should not be used repeatedly.
+///
+/// The previous value stays readable until the new one is completely written. The next increment
+/// after this leaves the value as it is.
+pub fn set_counter<F: FlashStorage>(flash: &mut F, value: u32) -> Result<(), F::Error> {
+    let active = find_active_sector(flash)?;
+    start_sector(flash, active.as_ref(), value, true, None)
+}
+
+/// Overwrite counter so that `value` applies right away and the next increment moves on from it,
+/// for a board that stays powered between pulses. Costs one sector erase like `set_counter`.
+pub fn set_counter_now<F: FlashStorage>(flash: &mut F, value: u32) -> Result<(), F::Error> {
+    let active = find_active_sector(flash)?;
+    /* The line keeps alternating as before, so the phase flips with the parity of the jump. */
+    let phase = active.as_ref().and_then(|active| {
+        let jump_odd = (value ^ active.value()) & 1 == 1;
+        active.polarity_phase.map(|phase| phase ^ jump_odd)
+    });
+    start_sector(flash, active.as_ref(), value, false, phase)
+}
+
+/// Check the active sector and, if its offset region is corrupted, rewrite the counter cleanly into
+/// the next sector. Returns what was wrong.
+pub fn repair_counter<F: FlashStorage>(flash: &mut F) -> Result<Option<Corruption>, F::Error> {
+    let active = match find_active_sector(flash)? {
+        Some(active) => active,
+        None => return Ok(None),
+    };
+    if active.corruption.is_some() {
+        let phase = active.polarity_phase.filter(|_| !active.pending_set);
+        start_sector(
+            flash,
+            Some(&active),
+            active.value(),
+            active.pending_set,
+            phase,
+        )?;
+    }
+    Ok(active.corruption)
+}
+
+/// Store the polarity phase of the pulse line, if the active sector does not have one yet.
+pub fn set_polarity_phase<F: FlashStorage>(flash: &mut F, phase: bool) -> Result<(), F::Error> {
+    match find_active_sector(flash)? {
+        Some(active) if active.polarity_phase.is_none() => {
+            write_polarity_marker(flash, active.index, phase)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Count one corrected missed pulse.
+pub fn record_correction<F: FlashStorage>(flash: &mut F) -> Result<(), F::Error> {
+    let active = match find_active_sector(flash)? {
+        Some(active) => active,
+        None => return Ok(()),
+    };
+    /* The count saturates when the region is full. The header total catches up at the next
+     * rollover only by what fit, which is plenty for an event this rare. */
+    let bit = active.corrections_in_sector;
+    if bit >= (OFFSET_REGION_START - CORRECTIONS_START) * 8 {
+        return Ok(());
+    }
+    let byte_index = sector_start(active.index) + CORRECTIONS_START + bit / 8;
+    let mut byte = [0_u8];
+    flash.read(byte_index, &mut byte)?;
+    flash.write(byte_index, &[byte[0] & !(0x80 >> (bit % 8))])
+}
+
+/// Number of sectors in the counter ring.
+pub fn sector_count<F: FlashStorage>(flash: &F) -> u32 {
+    flash.size() / SECTOR_SIZE
+}
+
+/// Read the header of every sector in the ring. Sectors without a valid header are `None`.
+pub fn read_headers<F: FlashStorage>(flash: &F) -> Result<Vec<Option<Header>>, F::Error> {
+    let mut buffer = [0_u8; HEADER_SIZE];
+    (0..sector_count(flash))
+        .map(|index| {
+            flash.read(sector_start(index), &mut buffer)?;
+            Ok(Header::decode(&buffer))
+        })
+        .collect()
+}
+
+/// Erase count of every sector, as recorded in its header. `None` for sectors that have never held
+/// a valid header.
+pub fn erase_counts<F: FlashStorage>(flash: &F) -> Result<Vec<Option<u32>>, F::Error> {
+    Ok(read_headers(flash)?
+        .into_iter()
+        .map(|header| header.map(|h| h.erase_count))
+        .collect())
+}
+
+/// Locate the sector with the newest valid header and decode its offset region.
+pub fn find_active_sector<F: FlashStorage>(flash: &F) -> Result<Option<ActiveSector>, F::Error> {
+    assert!(sector_count(flash) >= 2, "counter partition too small");
+
+    let newest = read_headers(flash)?
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, header)| Some((index as u32, header?)))
+        .max_by_key(|(_, header)| header.sequence);
+    let (index, header) = match newest {
+        Some(newest) => newest,
+        None => return Ok(None),
+    };
+
+    let mut offset_region = vec![0_u8; (SECTOR_SIZE - OFFSET_REGION_START) as usize];
+    flash.read(sector_start(index) + OFFSET_REGION_START, &mut offset_region)?;
+    let (offset, corruption) = check_offset_region(&offset_region);
+    let head_index = offset_region.iter().position(|&x| x != 0).unwrap_or(offset_region.len());
+    let head_byte = offset_region.get(head_index).copied();
+
+    let mut markers = [0_u8; (OFFSET_REGION_START - PENDING_SET_MARKER) as usize];
+    flash.read(sector_start(index) + PENDING_SET_MARKER, &mut markers)?;
+    let marker = |position: u32| markers[(position - PENDING_SET_MARKER) as usize];
+    let pending_set = header.pending_set && marker(PENDING_SET_MARKER) == 0xFF;
+    let polarity_phase = match marker(POLARITY_MARKER) {
+        POLARITY_PHASE_CLEAR => Some(false),
+        POLARITY_PHASE_SET => Some(true),
+        _ => None,
+    };
+    let corrections_region = &markers[(CORRECTIONS_START - PENDING_SET_MARKER) as usize..];
+    let (corrections_in_sector, _) = check_offset_region(corrections_region);
+
+    Ok(Some(ActiveSector {
+        index,
+        header,
+        offset,
+        corruption,
+        pending_set,
+        polarity_phase,
+        corrections_in_sector,
+        head_index: head_index as u32,
+        head_byte,
+    }))
+}
+
+/// Start the sector after the active one in the ring, holding `value`.
+///
+/// The polarity phase is carried over, unless the counter is being set, which changes the parity
+/// of the value.
+fn start_sector<F: FlashStorage>(
+    flash: &mut F,
+    active: Option<&ActiveSector>,
+    value: u32,
+    pending_set: bool,
+    polarity_phase: Option<bool>,
+) -> Result<(), F::Error> {
+    let (index, sequence) = match active {
+        Some(active) => (
+            (active.index + 1) % sector_count(flash),
+            active.header.sequence + 1,
+        ),
+        None => (0, 0),
+    };
+
+    /* Carry the erase count of the sector over. A blank sector has not been used yet. If the
+     * header has been damaged instead, the sectors are used in order, so the sector is one lap
+     * behind the active one unless the ring just wrapped around. */
+    let mut buffer = [0_u8; HEADER_SIZE];
+    flash.read(sector_start(index), &mut buffer)?;
+    let previous_erase_count = match (Header::decode(&buffer), active) {
+        (Some(previous), _) => previous.erase_count,
+        _ if buffer.iter().all(|&b| b == 0xFF) => 0,
+        (None, Some(active)) if index > active.index => active.header.erase_count.saturating_sub(1),
+        (None, Some(active)) => active.header.erase_count,
+        (None, None) => 0,
+    };
+
+    let header = Header {
+        sequence,
+        base: value,
+        erase_count: previous_erase_count + 1,
+        pending_set,
+        corrections: active.map_or(0, ActiveSector::corrections),
+    };
+    flash.erase(sector_start(index), SECTOR_SIZE)?;
+    flash.write(sector_start(index), &header.encode())?;
+
+    match polarity_phase {
+        Some(phase) => write_polarity_marker(flash, index, phase),
+        None => Ok(()),
+    }
+}
+
+fn write_polarity_marker<F: FlashStorage>(
+    flash: &mut F,
+    index: u32,
+    phase: bool,
+) -> Result<(), F::Error> {
+    let marker = if phase {
+        POLARITY_PHASE_SET
+    } else {
+        POLARITY_PHASE_CLEAR
+    };
+    flash.write(sector_start(index) + POLARITY_MARKER, &[marker])
+}
+
+fn sector_start(index: u32) -> u32 {
+    index * SECTOR_SIZE
+}
+
+/// Read the counter format used before the ring was introduced: a 4-byte base followed by the
+/// unary offset, filling one sector. The old partition ended where the current one does, so that
+/// sector is the last sector of the partition.
+///
+/// An erased base means either a fresh partition or an interrupted `set_counter`, and reads as 0.
+fn read_legacy_counter<F: FlashStorage>(flash: &F) -> Result<u32, F::Error> {
+    let mut buffer = vec![0_u8; SECTOR_SIZE as usize];
+    flash.read(flash.size() - SECTOR_SIZE, &mut buffer)?;
+
+    let base = u32::from_be_bytes(buffer[0..4].try_into().unwrap());
+    if base == u32::MAX {
+        return Ok(0);
+    }
+    let offset_region = &buffer[4..];
+    let head_index = offset_region.partition_point(|&x| x == 0);
+    let head_bits = offset_region.get(head_index).map_or(0, |b| b.leading_zeros());
+    /* A damaged base close to the top must not wrap around to a small value. */
+    Ok(base.saturating_add(head_index as u32 * 8 + head_bits))
+}
@@ -0,0 +1,14 @@
+/// CRC-32 (IEEE 802.3), the same checksum zlib and PNG use.
+///
+/// Bitwise implementation: slow, but only ever run over a few small records per boot.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
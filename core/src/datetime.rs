@@ -0,0 +1,88 @@
+use chrono::{DateTime, NaiveDateTime};
+
+use crate::{
+    config,
+    counter::{set_counter, set_counter_now},
+    flash::FlashStorage,
+};
+
+/// How the master clock line advances the time, i.e. what one counter increment means.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PulseProtocol {
+    /// Seconds per pulse.
+    pub increment: u32,
+    /// Unix timestamp of counter value 0.
+    pub epoch: i64,
+}
+
+impl PulseProtocol {
+    pub const MINUTE: PulseProtocol = PulseProtocol {
+        increment: 60,
+        epoch: 0,
+    };
+    pub const HALF_MINUTE: PulseProtocol = PulseProtocol {
+        increment: 30,
+        epoch: 0,
+    };
+    /// Counts from 2000, as seconds since 1970 would run out of 32 bits in 2106.
+    pub const SECOND: PulseProtocol = PulseProtocol {
+        increment: 1,
+        epoch: 946_684_800,
+    };
+
+    pub fn datetime_from_counter(self, counter: u32) -> NaiveDateTime {
+        let seconds = self.epoch + i64::from(counter) * i64::from(self.increment);
+        DateTime::from_timestamp(seconds, 0)
+            .expect("counter out of datetime range")
+            .naive_utc()
+    }
+
+    /// Counter value for `datetime`, rounded down to a whole increment and saturated to the range
+    /// of the counter.
+    pub fn counter_from_datetime(self, datetime: NaiveDateTime) -> u32 {
+        let increments =
+            (datetime.and_utc().timestamp() - self.epoch).div_euclid(i64::from(self.increment));
+        increments.clamp(0, u32::MAX.into()) as u32
+    }
+
+    pub fn clamp_datetime_to_counter(self, datetime: NaiveDateTime) -> NaiveDateTime {
+        self.datetime_from_counter(self.counter_from_datetime(datetime))
+    }
+}
+
+/// Time for `counter` in the configured pulse protocol.
+pub fn datetime_from_counter(counter: u32) -> NaiveDateTime {
+    config::PULSE_PROTOCOL.datetime_from_counter(counter)
+}
+
+pub fn counter_from_datetime(datetime: NaiveDateTime) -> u32 {
+    config::PULSE_PROTOCOL.counter_from_datetime(datetime)
+}
+
+pub fn clamp_datetime_to_counter(datetime: NaiveDateTime) -> NaiveDateTime {
+    config::PULSE_PROTOCOL.clamp_datetime_to_counter(datetime)
+}
+
+/// When a saved time is shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveTiming {
+    /// At the next pulse, which powers up a pulse-powered board.
+    NextPulse,
+    /// Right away, on a board counting pulses while it stays powered.
+    Now,
+}
+
+/// Store `datetime` as the counter value, as done by the Save field in adjust mode. Returns the
+/// time actually stored after clamping to the counter range.
+pub fn save_datetime<F: FlashStorage>(
+    flash: &mut F,
+    datetime: NaiveDateTime,
+    timing: SaveTiming,
+) -> Result<NaiveDateTime, F::Error> {
+    let value = counter_from_datetime(datetime);
+    match timing {
+        SaveTiming::NextPulse => set_counter(flash, value)?,
+        SaveTiming::Now => set_counter_now(flash, value)?,
+    }
+    Ok(datetime_from_counter(value))
+}
@@ -0,0 +1,230 @@
+//! Log of boots and counter changes in the `log` partition, for finding out afterwards why the
+//! clock shows the wrong time.
+//!
+//! A boot is logged as soon as the counter has been handled, and again once the display has been
+//! drawn, so a boot cut short by the pulse ending shows up as a `Boot` without a `Drawn`.
+
+use std::fmt;
+
+use crate::{flash::FlashStorage, reset::ResetReason, ring};
+
+const RECORD_SIZE: usize = 16;
+
+const KIND_BOOT: u8 = 1;
+const KIND_DRAWN: u8 = 2;
+const KIND_SET: u8 = 3;
+const KIND_PULSE: u8 = 4;
+
+const FLAG_PULSE_BOOT: u8 = 1 << 0;
+const FLAG_REPAIRED: u8 = 1 << 1;
+const FLAG_CORRECTED: u8 = 1 << 2;
+const FLAG_CATCHING_UP: u8 = 1 << 3;
+
+const FLAG_PENDING_BEFORE: u8 = 1 << 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetSource {
+    Buttons,
+    Console,
+    /// Undoing the previous set.
+    Undo,
+}
+
+impl SetSource {
+    pub fn code(self) -> u8 {
+        match self {
+            SetSource::Buttons => 0,
+            SetSource::Console => 1,
+            SetSource::Undo => 2,
+        }
+    }
+
+    pub fn from_code(code: u8) -> SetSource {
+        match code {
+            0 => SetSource::Buttons,
+            1 => SetSource::Console,
+            _ => SetSource::Undo,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Boot {
+        reset_reason: ResetReason,
+        /// Counter value shown by this boot.
+        counter: u32,
+        pulse_boot: bool,
+        /// The counter was corrupted and has been rewritten.
+        repaired: bool,
+        /// A missed pulse was detected from the polarity and added.
+        corrected: bool,
+        catching_up: bool,
+    },
+    /// The display was drawn, `total_us` after the app started.
+    Drawn { total_us: u32 },
+    /// The counter was set from `old` to `new`.
+    Set {
+        old: u32,
+        new: u32,
+        source: SetSource,
+        /// `old` was itself a set still waiting for its first pulse.
+        pending_before: bool,
+    },
+    /// A pulse was counted from the pulse input while running.
+    Pulse { counter: u32 },
+}
+
+impl Event {
+    /// Layout: kind, two bytes depending on the kind, a reserved byte and three big-endian
+    /// integers.
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0xFF; RECORD_SIZE];
+        let mut put = |position: usize, value: u32| {
+            bytes[position..position + 4].copy_from_slice(&value.to_be_bytes())
+        };
+        let (kind, a, b) = match *self {
+            Event::Boot {
+                reset_reason,
+                counter,
+                pulse_boot,
+                repaired,
+                corrected,
+                catching_up,
+            } => {
+                put(4, counter);
+                let flags = [
+                    (pulse_boot, FLAG_PULSE_BOOT),
+                    (repaired, FLAG_REPAIRED),
+                    (corrected, FLAG_CORRECTED),
+                    (catching_up, FLAG_CATCHING_UP),
+                ]
+                .iter()
+                .filter(|(set, _)| *set)
+                .fold(0, |flags, (_, flag)| flags | flag);
+                (KIND_BOOT, reset_reason.code(), flags)
+            }
+            Event::Drawn { total_us } => {
+                put(4, total_us);
+                (KIND_DRAWN, 0xFF, 0xFF)
+            }
+            Event::Set {
+                old,
+                new,
+                source,
+                pending_before,
+            } => {
+                put(4, old);
+                put(8, new);
+                let flags = if pending_before {
+                    FLAG_PENDING_BEFORE
+                } else {
+                    0
+                };
+                (KIND_SET, source.code(), flags)
+            }
+            Event::Pulse { counter } => {
+                put(4, counter);
+                (KIND_PULSE, 0xFF, 0xFF)
+            }
+        };
+        bytes[0] = kind;
+        bytes[1] = a;
+        bytes[2] = b;
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Event> {
+        if bytes.len() != RECORD_SIZE {
+            return None;
+        }
+        let get =
+            |position: usize| u32::from_be_bytes(bytes[position..position + 4].try_into().unwrap());
+        let flags = bytes[2];
+        let event = match bytes[0] {
+            KIND_BOOT => Event::Boot {
+                reset_reason: ResetReason::from_code(bytes[1]),
+                counter: get(4),
+                pulse_boot: flags & FLAG_PULSE_BOOT != 0,
+                repaired: flags & FLAG_REPAIRED != 0,
+                corrected: flags & FLAG_CORRECTED != 0,
+                catching_up: flags & FLAG_CATCHING_UP != 0,
+            },
+            KIND_DRAWN => Event::Drawn { total_us: get(4) },
+            KIND_SET => Event::Set {
+                old: get(4),
+                new: get(8),
+                source: SetSource::from_code(bytes[1]),
+                pending_before: flags & FLAG_PENDING_BEFORE != 0,
+            },
+            KIND_PULSE => Event::Pulse { counter: get(4) },
+            _ => return None,
+        };
+        Some(event)
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Event::Boot {
+                reset_reason,
+                counter,
+                pulse_boot,
+                repaired,
+                corrected,
+                catching_up,
+            } => {
+                write!(f, "boot {reset_reason:?}, counter {counter}")?;
+                for (set, name) in [
+                    (pulse_boot, "pulse"),
+                    (repaired, "repaired"),
+                    (corrected, "corrected"),
+                    (catching_up, "catching up"),
+                ] {
+                    if set {
+                        write!(f, ", {name}")?;
+                    }
+                }
+                Ok(())
+            }
+            Event::Drawn { total_us } => write!(f, "drawn after {} ms", total_us / 1000),
+            Event::Set {
+                old, new, source, ..
+            } => {
+                let source = match source {
+                    SetSource::Buttons => "with the buttons",
+                    SetSource::Console => "on the console",
+                    SetSource::Undo => "by undo",
+                };
+                write!(f, "set from {old} to {new} {source}")
+            }
+            Event::Pulse { counter } => write!(f, "pulse, counter {counter}"),
+        }
+    }
+}
+
+pub fn log_event<F: FlashStorage>(flash: &mut F, event: &Event) -> Result<(), F::Error> {
+    ring::append(flash, &event.encode())
+}
+
+/// Logged events, oldest first.
+pub fn read_events<F: FlashStorage>(flash: &F) -> Result<Vec<Event>, F::Error> {
+    let records = ring::read_records(flash, RECORD_SIZE)?;
+    Ok(records
+        .iter()
+        .filter_map(|record| Event::decode(record))
+        .collect())
+}
+
+/// The latest `count` logged events, oldest first. Quicker than [`read_events`] for a few.
+pub fn read_latest_events<F: FlashStorage>(
+    flash: &F,
+    count: usize,
+) -> Result<Vec<Event>, F::Error> {
+    let records = ring::read_latest_records(flash, RECORD_SIZE, count)?;
+    Ok(records
+        .iter()
+        .filter_map(|record| Event::decode(record))
+        .collect())
+}
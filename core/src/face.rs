@@ -0,0 +1,41 @@
+//! The clock face shown between adjustments.
+
+use chrono::NaiveDateTime;
+
+use crate::fb::{self, Framebuffer, Paint, Rect};
+
+/// Badge shown when the counter was corrupted and has been rewritten on this boot.
+pub const REPAIRED_BADGE: &str = "!";
+
+/// Badge shown when a missed pulse was detected and added on this boot.
+pub const CORRECTED_BADGE: &str = "+1";
+
+/// Draw the time and date, with a status badge for each entry of `badges`.
+pub fn draw_clock_face(framebuffer: &mut Framebuffer, time: NaiveDateTime, badges: &[&str]) {
+    let time_string = time.format("%H:%M").to_string();
+    let date_string = time.format("%-d.%-m.%Y").to_string();
+
+    framebuffer.text_centered(Paint::Darken, fb::WIDTH / 2, 96, 90., "Aikamme");
+    framebuffer.text_centered(Paint::Darken, fb::WIDTH / 2, 405, 454., &time_string);
+    framebuffer.text_centered(Paint::Darken, fb::WIDTH / 2, 500, 90., &date_string);
+    for (slot, text) in badges.iter().enumerate() {
+        draw_badge(framebuffer, slot as i32, text);
+    }
+}
+
+/// Small inverted status indicator in the top right corner. `slot` counts leftwards.
+fn draw_badge(framebuffer: &mut Framebuffer, slot: i32, text: &str) {
+    const SIZE: i32 = 50;
+    const MARGIN: i32 = 20;
+    let x = fb::WIDTH - MARGIN - SIZE - slot * (SIZE + MARGIN);
+    framebuffer.rect(
+        Paint::Darken,
+        Rect {
+            x,
+            y: MARGIN,
+            w: SIZE,
+            h: SIZE,
+        },
+    );
+    framebuffer.text_centered(Paint::Lighten, x + SIZE / 2, MARGIN + 40, 40., text);
+}
@@ -0,0 +1,255 @@
+use std::{
+    convert::Infallible,
+    io::{self, Write},
+};
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{Dimensions, OriginDimensions, Size},
+    pixelcolor::{Gray8, GrayColor},
+    primitives::{PointsIter, Rectangle},
+    Pixel,
+};
+use rusttype::{self, Font, Point, Scale};
+
+pub const WIDTH: i32 = 960;
+pub const HEIGHT: i32 = 540;
+
+pub const WHITE: u8 = u8::MAX;
+pub const BLACK: u8 = u8::MIN;
+
+static FONT_DATA: &[u8] = include_bytes!("../font/Lato-Bold.subset.ttf") as &[u8];
+
+pub struct Framebuffer {
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Paint {
+    Darken,
+    Lighten,
+}
+
+#[derive(Clone, Copy)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Default for Framebuffer {
+    fn default() -> Framebuffer {
+        Framebuffer::new()
+    }
+}
+
+impl Framebuffer {
+    pub fn new() -> Framebuffer {
+        Framebuffer {
+            data: vec![WHITE; (WIDTH * HEIGHT) as usize],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.data.fill(WHITE);
+    }
+
+    /// Pixels row by row from the top left, 0 being black.
+    pub fn pixels(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Write as a binary PGM image, which most image viewers open.
+    pub fn write_pgm<W: Write>(&self, mut out: W) -> io::Result<()> {
+        write!(out, "P5\n{WIDTH} {HEIGHT}\n255\n")?;
+        out.write_all(&self.data)
+    }
+
+    pub fn inside(&self, x: i32, y: i32) -> bool {
+        (0..WIDTH).contains(&x) && (0..HEIGHT).contains(&y)
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> u8 {
+        let pos = y
+            .checked_mul(WIDTH)
+            .and_then(|i| i.checked_add(x))
+            .and_then(|i| i.try_into().ok())
+            .and_then(|i: usize| self.data.get(i))
+            .expect("position outside framebuffer");
+        *pos
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, val: u8) {
+        let pos = y
+            .checked_mul(WIDTH)
+            .and_then(|i| i.checked_add(x))
+            .and_then(|i| i.try_into().ok())
+            .and_then(|i: usize| self.data.get_mut(i))
+            .expect("position outside framebuffer");
+        *pos = val;
+    }
+
+    pub fn paint(&mut self, paint: Paint, x: i32, y: i32, val: u8) {
+        if self.inside(x, y) {
+            let before = self.get(x, y);
+            let after = match paint {
+                Paint::Darken => before.saturating_sub(val),
+                Paint::Lighten => before.saturating_add(val),
+            };
+            self.set(x, y, after);
+        }
+    }
+
+    pub fn rect(&mut self, paint: Paint, rect: Rect) {
+        let Rect { x, y, w, h } = rect;
+        for p_x in x..(x + w) {
+            for p_y in y..(y + h) {
+                self.paint(paint, p_x, p_y, u8::MAX);
+            }
+        }
+    }
+
+    /// Draw text centered horizontally. Vertically aligned to the baseline.
+    pub fn text_centered(&mut self, paint: Paint, x: i32, y: i32, size: f32, content: &str) {
+        let scale = Scale::uniform(size);
+
+        let glyphs: Vec<_> = FONT
+            .layout(content, scale, Point { x: 0., y: 0. })
+            .collect();
+
+        let min_x = glyphs
+            .first()
+            .map(|g| g.pixel_bounding_box().unwrap().min.x)
+            .unwrap();
+        let max_x = glyphs
+            .last()
+            .map(|g| g.pixel_bounding_box().unwrap().max.x)
+            .unwrap();
+        let glyphs_width = max_x - min_x;
+
+        let x = x - glyphs_width / 2 - min_x;
+
+        for glyph in &glyphs {
+            if let Some(bounding_box) = glyph.pixel_bounding_box() {
+                glyph.draw(|inner_x, inner_y, val| {
+                    let actual_x = inner_x as i32 + bounding_box.min.x + x;
+                    let actual_y = inner_y as i32 + bounding_box.min.y + y;
+                    let val = (val * 255.) as u8;
+                    self.paint(paint, actual_x, actual_y, val);
+                });
+            }
+        }
+    }
+}
+
+/// Drawing with `embedded-graphics` sets pixels to the given gray level, like [`Framebuffer::set`].
+/// Filling with a color has to be called as `DrawTarget::clear`, as [`Framebuffer::clear`] takes
+/// precedence.
+impl DrawTarget for Framebuffer {
+    type Color = Gray8;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Pixel<Gray8>>,
+    {
+        for Pixel(point, color) in pixels {
+            if self.inside(point.x, point.y) {
+                self.set(point.x, point.y, color.luma());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Gray8>,
+    {
+        let visible = area.intersection(&self.bounding_box());
+        if visible != *area {
+            /* Partly off screen, the colors of the clipped pixels have to be skipped. */
+            let pixels = area.points().zip(colors).map(|(p, c)| Pixel(p, c));
+            return self.draw_iter(pixels);
+        }
+        let mut colors = colors.into_iter();
+        for y in area.rows() {
+            let start = (y * WIDTH + area.top_left.x) as usize;
+            let row = &mut self.data[start..start + area.size.width as usize];
+            for (pixel, color) in row.iter_mut().zip(colors.by_ref()) {
+                *pixel = color.luma();
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Gray8) -> Result<(), Infallible> {
+        let visible = area.intersection(&self.bounding_box());
+        if let Some(bottom_right) = visible.bottom_right() {
+            for y in visible.rows() {
+                let start = (y * WIDTH + visible.top_left.x) as usize;
+                let end = (y * WIDTH + bottom_right.x + 1) as usize;
+                self.data[start..end].fill(color.luma());
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Gray8) -> Result<(), Infallible> {
+        self.data.fill(color.luma());
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Framebuffer {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl Framebuffer {
+    /// A draw target that combines `embedded-graphics` drawing with what is already there, like
+    /// [`Framebuffer::paint`]. Black darkens fully when darkening and white lightens fully when
+    /// lightening, so antialiased edges blend in.
+    pub fn painted(&mut self, paint: Paint) -> Painted<'_> {
+        Painted {
+            framebuffer: self,
+            paint,
+        }
+    }
+}
+
+/// See [`Framebuffer::painted`].
+pub struct Painted<'a> {
+    framebuffer: &'a mut Framebuffer,
+    paint: Paint,
+}
+
+impl DrawTarget for Painted<'_> {
+    type Color = Gray8;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Pixel<Gray8>>,
+    {
+        for Pixel(point, color) in pixels {
+            let amount = match self.paint {
+                Paint::Darken => u8::MAX - color.luma(),
+                Paint::Lighten => color.luma(),
+            };
+            self.framebuffer.paint(self.paint, point.x, point.y, amount);
+        }
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Painted<'_> {
+    fn size(&self) -> Size {
+        self.framebuffer.size()
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref FONT: Font<'static> = Font::try_from_bytes(FONT_DATA).expect("failed loading font");
+}
@@ -0,0 +1,172 @@
+use std::fmt::Debug;
+
+/// Size of the smallest erasable unit of the flash chip.
+pub const SECTOR_SIZE: u32 = 4096;
+
+/// Byte-addressed NOR flash storage.
+///
+/// Writes can only clear bits (1 -> 0). Setting bits back to 1 requires erasing, which works on
+/// whole sectors at a time.
+pub trait FlashStorage {
+    type Error: Debug;
+
+    fn size(&self) -> u32;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Erase `size` bytes starting at `offset`. Both must be multiples of [`SECTOR_SIZE`].
+    fn erase(&mut self, offset: u32, size: u32) -> Result<(), Self::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulatedFlashError {
+    OutOfBounds,
+    UnalignedErase,
+    /// A write tried to flip a bit from 0 to 1 without an erase.
+    SetBit { offset: u32 },
+    /// Simulated power cut, see [`SimulatedFlash::cut_power_after`].
+    PowerLoss,
+}
+
+/// In-memory flash that enforces NOR semantics and counts erases per sector.
+#[derive(Clone)]
+pub struct SimulatedFlash {
+    data: Vec<u8>,
+    erase_counts: Vec<u32>,
+    /// Number of writes and erases that still succeed before the power is cut.
+    power_budget: Option<u32>,
+    powered: bool,
+}
+
+impl SimulatedFlash {
+    /// New fully erased flash of `sectors` sectors.
+    pub fn new(sectors: u32) -> SimulatedFlash {
+        SimulatedFlash {
+            data: vec![0xFF; (sectors * SECTOR_SIZE) as usize],
+            erase_counts: vec![0; sectors as usize],
+            power_budget: None,
+            powered: true,
+        }
+    }
+
+    /// Flash with the given raw contents, e.g. a partition dump.
+    pub fn from_image(image: Vec<u8>) -> SimulatedFlash {
+        assert_eq!(image.len() as u32 % SECTOR_SIZE, 0);
+        let sectors = image.len() / SECTOR_SIZE as usize;
+        SimulatedFlash {
+            data: image,
+            erase_counts: vec![0; sectors],
+            power_budget: None,
+            powered: true,
+        }
+    }
+
+    /// Let `operations` more writes or erases complete, then cut the power in the middle of the
+    /// next one. The interrupted operation is left half done and every access fails with
+    /// [`SimulatedFlashError::PowerLoss`] until [`SimulatedFlash::restore_power`].
+    pub fn cut_power_after(&mut self, operations: u32) {
+        self.power_budget = Some(operations);
+    }
+
+    pub fn restore_power(&mut self) {
+        self.power_budget = None;
+        self.powered = true;
+    }
+
+    pub fn powered(&self) -> bool {
+        self.powered
+    }
+
+    /// Consume one operation from the power budget. Returns false if the power is cut during it.
+    fn spend_power(&mut self) -> Result<bool, SimulatedFlashError> {
+        if !self.powered {
+            return Err(SimulatedFlashError::PowerLoss);
+        }
+        match &mut self.power_budget {
+            Some(0) => {
+                self.powered = false;
+                Ok(false)
+            }
+            Some(budget) => {
+                *budget -= 1;
+                Ok(true)
+            }
+            None => Ok(true),
+        }
+    }
+
+    pub fn image(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn erase_counts(&self) -> &[u32] {
+        &self.erase_counts
+    }
+
+    fn range(&self, offset: u32, len: usize) -> Result<std::ops::Range<usize>, SimulatedFlashError> {
+        let start = offset as usize;
+        let end = start
+            .checked_add(len)
+            .ok_or(SimulatedFlashError::OutOfBounds)?;
+        if end > self.data.len() {
+            return Err(SimulatedFlashError::OutOfBounds);
+        }
+        Ok(start..end)
+    }
+}
+
+impl FlashStorage for SimulatedFlash {
+    type Error = SimulatedFlashError;
+
+    fn size(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        if !self.powered {
+            return Err(SimulatedFlashError::PowerLoss);
+        }
+        let range = self.range(offset, buffer.len())?;
+        buffer.copy_from_slice(&self.data[range]);
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), Self::Error> {
+        let range = self.range(offset, data.len())?;
+        for (i, (&old, &new)) in self.data[range.clone()].iter().zip(data).enumerate() {
+            if new & !old != 0 {
+                return Err(SimulatedFlashError::SetBit {
+                    offset: offset + i as u32,
+                });
+            }
+        }
+        if !self.spend_power()? {
+            /* Torn write: only the first half of the data makes it. */
+            let torn = data.len() / 2;
+            self.data[range.start..range.start + torn].copy_from_slice(&data[..torn]);
+            return Err(SimulatedFlashError::PowerLoss);
+        }
+        self.data[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn erase(&mut self, offset: u32, size: u32) -> Result<(), Self::Error> {
+        if offset % SECTOR_SIZE != 0 || size % SECTOR_SIZE != 0 {
+            return Err(SimulatedFlashError::UnalignedErase);
+        }
+        let range = self.range(offset, size as usize)?;
+        if !self.spend_power()? {
+            /* Interrupted erase: the first half is erased, the rest keeps its old contents. */
+            let torn = range.start + range.len() / 2;
+            self.data[range.start..torn].fill(0xFF);
+            return Err(SimulatedFlashError::PowerLoss);
+        }
+        self.data[range].fill(0xFF);
+        for sector in offset / SECTOR_SIZE..(offset + size) / SECTOR_SIZE {
+            self.erase_counts[sector as usize] += 1;
+        }
+        Ok(())
+    }
+}
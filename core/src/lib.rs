@@ -0,0 +1,49 @@
+//! Everything of the clock that does not touch the hardware: drawing, the counter and log
+//! encodings on top of [`flash::FlashStorage`], and the logic of adjust mode. Builds for the host
+//! as well as for the ESP32.
+
+pub mod fb;
+
+pub mod prepared;
+
+pub mod shapes;
+
+pub mod flash;
+
+pub mod crc;
+
+pub mod datetime;
+
+pub mod counter;
+
+pub mod config;
+
+pub mod reset;
+
+pub mod polarity;
+
+pub mod ring;
+
+pub mod profile;
+
+pub mod event_log;
+
+pub mod burst;
+
+pub mod startup;
+
+pub mod audit;
+
+pub mod console;
+
+pub mod face;
+
+pub mod pulse;
+
+pub mod buttons;
+
+pub mod adjust_model;
+
+pub mod adjust_view;
+
+pub mod adjust_flash;
@@ -0,0 +1,31 @@
+//! Missed pulse detection from the alternating polarity of the pulse line.
+//!
+//! The master clock reverses the polarity of every pulse. Relative to the counter, the polarity of
+//! the pulse showing value `n` is `phase XOR (n is odd)` for a fixed phase of the installation.
+//! The phase is learned from the first pulse seen and stored with the counter. If a pulse then
+//! arrives with the wrong polarity, the previous one was missed.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolarityCheck {
+    /// No phase was known, this is the phase to store.
+    Learned(bool),
+    Matched,
+    /// The polarity is wrong for `value`, so a pulse was missed and the counter needs one more
+    /// increment.
+    Missed,
+}
+
+/// Phase of a pulse with polarity `high` showing `value`.
+pub fn phase_of(value: u32, high: bool) -> bool {
+    high ^ (value % 2 == 1)
+}
+
+/// Check the polarity `high` of the pulse that caused this boot, now showing `value`.
+pub fn check(phase: Option<bool>, value: u32, high: bool) -> PolarityCheck {
+    let observed = phase_of(value, high);
+    match phase {
+        None => PolarityCheck::Learned(observed),
+        Some(phase) if phase == observed => PolarityCheck::Matched,
+        Some(_) => PolarityCheck::Missed,
+    }
+}
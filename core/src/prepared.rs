@@ -0,0 +1,119 @@
+//! Framebuffers packed into the pixel format of the display driver.
+//!
+//! Kept apart from the firmware's `paper` module, which talks to the driver, so that what would be
+//! sent to the display can be looked at on the host.
+
+use crate::fb::{Framebuffer, HEIGHT, WIDTH};
+
+/* Pixels are packed in pairs. */
+const _: () = assert!(WIDTH % 2 == 0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DrawMode {
+    DirectUpdateBinary,
+    Flashing,
+    NonFlashing,
+    FromWhiteQuick,
+    FromBlackQuick,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Packing {
+    /// Two pixels per byte, drawn over a white display.
+    TwoPerByte,
+    /// One byte per pixel, the old value in the low and the new one in the high nibble.
+    Difference,
+}
+
+pub struct PreparedFramebuffer {
+    packed: Vec<u8>,
+    draw_mode: DrawMode,
+    packing: Packing,
+}
+
+impl PreparedFramebuffer {
+    pub fn prepare(framebuffer: &Framebuffer, draw_mode: DrawMode) -> PreparedFramebuffer {
+        // TODO: use 8 bits per byte for binary mode?
+        let mut packed = vec![0; (WIDTH / 2 * HEIGHT) as usize];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH / 2 {
+                let packed_idx = (y * (WIDTH / 2) + x) as usize;
+                let l = framebuffer.get(2 * x, y);
+                let r = framebuffer.get(2 * x + 1, y);
+                let (l, r) = if matches!(draw_mode, DrawMode::DirectUpdateBinary) {
+                    (15 * (l >> 7), 15 * (r >> 7))
+                } else {
+                    (l >> 4, r >> 4)
+                };
+                let combined = r << 4 | l;
+                packed[packed_idx] = combined;
+            }
+        }
+        PreparedFramebuffer {
+            packed,
+            draw_mode,
+            packing: Packing::TwoPerByte,
+        }
+    }
+
+    pub fn prepare_difference(
+        from_framebuffer: &Framebuffer,
+        to_framebuffer: &Framebuffer,
+        draw_mode: DrawMode,
+    ) -> PreparedFramebuffer {
+        let mut packed = vec![0; (WIDTH * HEIGHT) as usize];
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let packed_idx = (y * WIDTH + x) as usize;
+                let from = from_framebuffer.get(x, y);
+                let to = to_framebuffer.get(x, y);
+                let (from, to) = if matches!(draw_mode, DrawMode::DirectUpdateBinary) {
+                    (15 * (from >> 7), 15 * (to >> 7))
+                } else {
+                    (from >> 4, to >> 4)
+                };
+                let combined = to << 4 | from;
+                packed[packed_idx] = combined;
+            }
+        }
+        PreparedFramebuffer {
+            packed,
+            draw_mode,
+            packing: Packing::Difference,
+        }
+    }
+
+    pub fn packed(&self) -> &[u8] {
+        &self.packed
+    }
+
+    pub fn draw_mode(&self) -> DrawMode {
+        self.draw_mode
+    }
+
+    pub fn packing(&self) -> Packing {
+        self.packing
+    }
+
+    /// The image the display shows once this has been drawn, at the 16 gray levels it can show.
+    pub fn decode(&self) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let level = match self.packing {
+                    Packing::TwoPerByte => {
+                        let byte = self.packed[(y * (WIDTH / 2) + x / 2) as usize];
+                        if x % 2 == 0 {
+                            byte & 0x0F
+                        } else {
+                            byte >> 4
+                        }
+                    }
+                    Packing::Difference => self.packed[(y * WIDTH + x) as usize] >> 4,
+                };
+                framebuffer.set(x, y, level * 17);
+            }
+        }
+        framebuffer
+    }
+}
@@ -0,0 +1,108 @@
+//! Durations of the phases of a pulse boot, kept in the `profile` partition.
+//!
+//! Everything has to finish while the pulse line keeps the board powered, about 2 s. Only boots
+//! that got as far as drawing leave a record.
+
+use std::fmt;
+
+use crate::{flash::FlashStorage, ring};
+
+const RECORD_SIZE: usize = 24;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BootTiming {
+    /// Repairing, reading and incrementing the counter.
+    pub counter_us: u32,
+    /// Drawing the clock face into the framebuffer.
+    pub render_us: u32,
+    /// `PreparedFramebuffer::prepare`.
+    pub prepare_us: u32,
+    pub quick_clear_us: u32,
+    pub draw_us: u32,
+    /// From startup of the app until the display has been drawn.
+    pub total_us: u32,
+}
+
+impl BootTiming {
+    fn fields(&self) -> [u32; 6] {
+        [
+            self.counter_us,
+            self.render_us,
+            self.prepare_us,
+            self.quick_clear_us,
+            self.draw_us,
+            self.total_us,
+        ]
+    }
+
+    fn from_fields(fields: [u32; 6]) -> BootTiming {
+        let [counter_us, render_us, prepare_us, quick_clear_us, draw_us, total_us] = fields;
+        BootTiming {
+            counter_us,
+            render_us,
+            prepare_us,
+            quick_clear_us,
+            draw_us,
+            total_us,
+        }
+    }
+
+    pub fn encode(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        for (chunk, field) in bytes.chunks_mut(4).zip(self.fields()) {
+            chunk.copy_from_slice(&field.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<BootTiming> {
+        if bytes.len() != RECORD_SIZE {
+            return None;
+        }
+        let mut fields = [0; 6];
+        for (field, chunk) in fields.iter_mut().zip(bytes.chunks(4)) {
+            *field = u32::from_be_bytes(chunk.try_into().unwrap());
+        }
+        Some(BootTiming::from_fields(fields))
+    }
+
+    /// The longest duration of each phase over `timings`, which need not come from the same boot.
+    pub fn worst_case(timings: &[BootTiming]) -> Option<BootTiming> {
+        let first = timings.first()?;
+        let mut worst = first.fields();
+        for timing in timings {
+            for (worst, field) in worst.iter_mut().zip(timing.fields()) {
+                *worst = (*worst).max(field);
+            }
+        }
+        Some(BootTiming::from_fields(worst))
+    }
+}
+
+impl fmt::Display for BootTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "counter {} ms, render {} ms, prepare {} ms, quick clear {} ms, draw {} ms, total {} ms",
+            self.counter_us / 1000,
+            self.render_us / 1000,
+            self.prepare_us / 1000,
+            self.quick_clear_us / 1000,
+            self.draw_us / 1000,
+            self.total_us / 1000
+        )
+    }
+}
+
+pub fn record_timing<F: FlashStorage>(flash: &mut F, timing: &BootTiming) -> Result<(), F::Error> {
+    ring::append(flash, &timing.encode())
+}
+
+/// Recorded boot timings, oldest first.
+pub fn read_timings<F: FlashStorage>(flash: &F) -> Result<Vec<BootTiming>, F::Error> {
+    let records = ring::read_records(flash, RECORD_SIZE)?;
+    Ok(records
+        .iter()
+        .filter_map(|record| BootTiming::decode(record))
+        .collect())
+}
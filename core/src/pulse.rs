@@ -0,0 +1,79 @@
+//! Counting pulses from the pulse line while the board stays powered.
+
+use std::mem;
+
+/// How the board is powered, which decides how pulses are counted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerMode {
+    /// Each pulse powers up the board, every power-on is counted.
+    PulsePowered,
+    /// The board is powered permanently and counts edges of the pulse input.
+    Continuous,
+    /// Count power-ons, and switch to counting edges once a pulse arrives while running.
+    Auto,
+}
+
+/// Recognizes pulses from the edges of a bouncy pulse input.
+///
+/// A pulse is counted when the input goes inactive after having been active for at least
+/// `min_length_us`. Pulses closer than `holdoff_us` to the previous one are bounces or dropouts
+/// within the same pulse and are ignored. The input being active when the detector is created does
+/// not count, since that is the pulse that powered up the board, and its end starts the holdoff.
+pub struct PulseDetector {
+    min_length_us: i64,
+    holdoff_us: i64,
+    /// Time the input last went active, if it is active now.
+    active_since_us: Option<i64>,
+    last_pulse_us: Option<i64>,
+    /// No edge has been fed yet.
+    first_edge: bool,
+}
+
+impl PulseDetector {
+    pub fn new(min_length_us: i64, holdoff_us: i64) -> PulseDetector {
+        PulseDetector {
+            min_length_us,
+            holdoff_us,
+            active_since_us: None,
+            last_pulse_us: None,
+            first_edge: true,
+        }
+    }
+
+    /// Feed an edge of the input. Returns true if it completed a pulse.
+    pub fn edge(&mut self, time_us: i64, active: bool) -> bool {
+        let first_edge = mem::replace(&mut self.first_edge, false);
+        if active {
+            self.active_since_us.get_or_insert(time_us);
+            return false;
+        }
+        let since_us = match self.active_since_us.take() {
+            Some(since_us) => since_us,
+            /* The end of a pulse that started before the detector. Its bounces are held off like
+             * those of a counted pulse. */
+            None if first_edge => {
+                self.last_pulse_us = Some(time_us);
+                return false;
+            }
+            None => return false,
+        };
+        if time_us - since_us < self.min_length_us {
+            return false;
+        }
+        if matches!(self.last_pulse_us, Some(last) if time_us - last < self.holdoff_us) {
+            return false;
+        }
+        self.last_pulse_us = Some(time_us);
+        true
+    }
+}
+
+/// Whether pulses should be counted from the input instead of from power-ons, given whether a
+/// pulse has been seen while running.
+pub fn counts_edges(mode: PowerMode, pulse_seen_while_running: bool) -> bool {
+    match mode {
+        PowerMode::PulsePowered => false,
+        PowerMode::Continuous => true,
+        PowerMode::Auto => pulse_seen_while_running,
+    }
+}
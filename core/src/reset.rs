@@ -0,0 +1,70 @@
+//! Reasons for a reset, in a form that can be stored in the boot log, and which of them count as
+//! a pulse.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetReason {
+    /// Power applied to the chip. Also reported for resets through the EN pin, which is what
+    /// espflash uses.
+    PowerOn,
+    External,
+    Software,
+    Panic,
+    InterruptWatchdog,
+    TaskWatchdog,
+    OtherWatchdog,
+    DeepSleep,
+    Brownout,
+    Sdio,
+    Unknown,
+}
+
+impl ResetReason {
+    pub const ALL: [ResetReason; 11] = [
+        ResetReason::PowerOn,
+        ResetReason::External,
+        ResetReason::Software,
+        ResetReason::Panic,
+        ResetReason::InterruptWatchdog,
+        ResetReason::TaskWatchdog,
+        ResetReason::OtherWatchdog,
+        ResetReason::DeepSleep,
+        ResetReason::Brownout,
+        ResetReason::Sdio,
+        ResetReason::Unknown,
+    ];
+
+    pub fn code(self) -> u8 {
+        ResetReason::ALL
+            .iter()
+            .position(|&reason| reason == self)
+            .unwrap() as u8
+    }
+
+    pub fn from_code(code: u8) -> ResetReason {
+        ResetReason::ALL
+            .get(code as usize)
+            .copied()
+            .unwrap_or(ResetReason::Unknown)
+    }
+}
+
+/// Whether this boot should advance the counter.
+///
+/// Only a power-on can be a minute pulse. Every other reset happens while the board is already
+/// powered, so the pulse that powered it has been counted already. A power-on while USB power is
+/// detected is someone plugging in the board to adjust it.
+///
+/// This also keeps a supply sag during a pulse from counting the pulse twice, as the board comes
+/// back up from a brownout. If the power drops out completely instead, nothing survives that
+/// could tell the second power-on from the next pulse, so it is counted.
+///
+/// The ESP32 reports a reset through the EN pin as a power-on, and that is how espflash and the
+/// reset button restart the board. Without `usb_powered`, such a reset can't be told from a pulse
+/// and advances the counter.
+pub fn is_pulse_boot(reset_reason: ResetReason, usb_powered: Option<bool>) -> bool {
+    match (reset_reason, usb_powered) {
+        (_, Some(true)) => false,
+        (ResetReason::PowerOn, _) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,170 @@
+//! Append-only ring of fixed-size records in flash.
+//!
+//! Like the counter, the partition is used as a ring of sectors, each starting with a checksummed
+//! header holding a sequence number. Records are written into the first erased slot of the newest
+//! sector, each followed by its own CRC-32. When the newest sector is full, the oldest one is
+//! erased and continues the ring. A write cut short by power loss leaves a slot that fails its
+//! checksum and is skipped, so older records are never affected.
+
+use crate::{
+    crc::crc32,
+    flash::{FlashStorage, SECTOR_SIZE},
+};
+
+const MAGIC: [u8; 4] = *b"PSLR";
+const VERSION: u8 = 1;
+
+const HEADER_SIZE: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Header {
+    sequence: u32,
+    record_size: u8,
+}
+
+impl Header {
+    /// Layout: magic, version, record size, 2 reserved bytes, sequence, CRC-32 of the bytes before
+    /// it. Integers are big-endian.
+    fn encode(&self) -> [u8; HEADER_SIZE as usize] {
+        let mut bytes = [0xFF; HEADER_SIZE as usize];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4] = VERSION;
+        bytes[5] = self.record_size;
+        bytes[8..12].copy_from_slice(&self.sequence.to_be_bytes());
+        let crc = crc32(&bytes[..12]);
+        bytes[12..16].copy_from_slice(&crc.to_be_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Header> {
+        let bytes = bytes.get(..HEADER_SIZE as usize)?;
+        let crc = u32::from_be_bytes(bytes[12..16].try_into().unwrap());
+        if bytes[0..4] != MAGIC || bytes[4] != VERSION || crc32(&bytes[..12]) != crc {
+            return None;
+        }
+        Some(Header {
+            sequence: u32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+            record_size: bytes[5],
+        })
+    }
+}
+
+fn slot_size(record_size: usize) -> u32 {
+    record_size as u32 + 4
+}
+
+fn slots_per_sector(record_size: usize) -> u32 {
+    (SECTOR_SIZE - HEADER_SIZE) / slot_size(record_size)
+}
+
+fn slot_start(sector: u32, slot: u32, record_size: usize) -> u32 {
+    sector * SECTOR_SIZE + HEADER_SIZE + slot * slot_size(record_size)
+}
+
+/// Sectors holding records of `record_size` bytes, oldest first. Sectors written with another
+/// record size are left out.
+fn sectors<F: FlashStorage>(flash: &F, record_size: usize) -> Result<Vec<(u32, Header)>, F::Error> {
+    let mut buffer = [0_u8; HEADER_SIZE as usize];
+    let mut sectors = Vec::new();
+    for index in 0..flash.size() / SECTOR_SIZE {
+        flash.read(index * SECTOR_SIZE, &mut buffer)?;
+        match Header::decode(&buffer) {
+            Some(header) if header.record_size as usize == record_size => {
+                sectors.push((index, header))
+            }
+            _ => {}
+        }
+    }
+    sectors.sort_by_key(|(_, header)| header.sequence);
+    Ok(sectors)
+}
+
+/// Read all intact records of `record_size` bytes, oldest first.
+pub fn read_records<F: FlashStorage>(
+    flash: &F,
+    record_size: usize,
+) -> Result<Vec<Vec<u8>>, F::Error> {
+    let mut records = Vec::new();
+    for (index, _) in sectors(flash, record_size)? {
+        records.extend(sector_records(flash, index, record_size)?);
+    }
+    Ok(records)
+}
+
+/// Read the latest `count` intact records of `record_size` bytes, oldest first. Only the newest
+/// sectors needed for them are read.
+pub fn read_latest_records<F: FlashStorage>(
+    flash: &F,
+    record_size: usize,
+    count: usize,
+) -> Result<Vec<Vec<u8>>, F::Error> {
+    let mut records = Vec::new();
+    for (index, _) in sectors(flash, record_size)?.into_iter().rev() {
+        if records.len() >= count {
+            break;
+        }
+        let mut older = sector_records(flash, index, record_size)?;
+        older.append(&mut records);
+        records = older;
+    }
+    Ok(records.split_off(records.len().saturating_sub(count)))
+}
+
+/// Intact records in sector `index`, oldest first.
+fn sector_records<F: FlashStorage>(
+    flash: &F,
+    index: u32,
+    record_size: usize,
+) -> Result<Vec<Vec<u8>>, F::Error> {
+    let mut records = Vec::new();
+    let mut slot = vec![0_u8; slot_size(record_size) as usize];
+    for position in 0..slots_per_sector(record_size) {
+        flash.read(slot_start(index, position, record_size), &mut slot)?;
+        /* An erased slot can pass the checksum, e.g. the CRC-32 of 4 bytes of 0xFF is
+         * 0xFFFFFFFF. */
+        if slot.iter().all(|&b| b == 0xFF) {
+            continue;
+        }
+        let (record, crc) = slot.split_at(record_size);
+        if crc32(record) == u32::from_be_bytes(crc.try_into().unwrap()) {
+            records.push(record.to_vec());
+        }
+    }
+    Ok(records)
+}
+
+/// Append `record` to the ring, erasing the oldest sector if the newest one is full.
+pub fn append<F: FlashStorage>(flash: &mut F, record: &[u8]) -> Result<(), F::Error> {
+    assert!(record.len() < u8::MAX as usize, "record too large");
+    assert!(flash.size() / SECTOR_SIZE >= 2, "ring partition too small");
+    let record_size = record.len();
+    let mut data = record.to_vec();
+    data.extend_from_slice(&crc32(record).to_be_bytes());
+
+    let newest = sectors(flash, record_size)?.last().copied();
+    if let Some((index, _)) = newest {
+        let mut slot = vec![0_u8; data.len()];
+        for position in 0..slots_per_sector(record_size) {
+            let start = slot_start(index, position, record_size);
+            flash.read(start, &mut slot)?;
+            if slot.iter().all(|&b| b == 0xFF) {
+                return flash.write(start, &data);
+            }
+        }
+    }
+
+    let (index, sequence) = match newest {
+        Some((index, header)) => (
+            (index + 1) % (flash.size() / SECTOR_SIZE),
+            header.sequence + 1,
+        ),
+        None => (0, 0),
+    };
+    let header = Header {
+        sequence,
+        record_size: record_size as u8,
+    };
+    flash.erase(index * SECTOR_SIZE, SECTOR_SIZE)?;
+    flash.write(index * SECTOR_SIZE, &header.encode())?;
+    flash.write(slot_start(index, 0, record_size), &data)
+}
@@ -0,0 +1,257 @@
+//! Antialiased lines, circles, arcs and polygons.
+//!
+//! Coordinates are in pixels, with pixel `(x, y)` covering the square from `(x, y)` to
+//! `(x + 1, y + 1)`. Each pixel is painted once per shape with how much of it the shape covers,
+//! so darkening with overlapping parts of one shape doesn't add up.
+
+use std::f32::consts::TAU;
+
+use crate::fb::{Framebuffer, Paint, HEIGHT, WIDTH};
+
+/// Vertical samples per pixel row when filling polygons. Coverage across a row is exact.
+const POLYGON_SUBSAMPLES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    pub fn new(x: f32, y: f32) -> Point {
+        Point { x, y }
+    }
+
+    fn length(self) -> f32 {
+        self.x.hypot(self.y)
+    }
+}
+
+impl std::ops::Sub for Point {
+    type Output = Point;
+
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Style {
+    Fill,
+    /// Outline centered on the edge of the shape.
+    Stroke {
+        width: f32,
+    },
+}
+
+/// Which parts of a self-intersecting polygon are inside.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    /// Inside where a ray to the outside crosses an odd number of edges.
+    EvenOdd,
+    /// Inside where the edges wind around the point at all.
+    NonZero,
+}
+
+impl Framebuffer {
+    /// Line of `width` with round ends.
+    pub fn line(&mut self, paint: Paint, from: Point, to: Point, width: f32) {
+        let margin = width / 2. + 1.;
+        let bounds = (
+            from.x.min(to.x) - margin,
+            from.y.min(to.y) - margin,
+            from.x.max(to.x) + margin,
+            from.y.max(to.y) + margin,
+        );
+        self.shade(paint, bounds, |p| {
+            stroke_coverage(segment_distance(p, from, to), width)
+        });
+    }
+
+    pub fn circle(&mut self, paint: Paint, center: Point, radius: f32, style: Style) {
+        self.arc(paint, center, radius, 0., TAU, style);
+    }
+
+    /// Part of a circle from angle `start` on for `sweep`, in radians. Angles grow clockwise from
+    /// the right, so `-TAU / 4.` points up. Filled arcs are pie slices and stroked arcs have round
+    /// ends.
+    pub fn arc(
+        &mut self,
+        paint: Paint,
+        center: Point,
+        radius: f32,
+        start: f32,
+        sweep: f32,
+        style: Style,
+    ) {
+        let margin = match style {
+            Style::Fill => 1.,
+            Style::Stroke { width } => width / 2. + 1.,
+        };
+        let extent = radius + margin;
+        let bounds = (
+            center.x - extent,
+            center.y - extent,
+            center.x + extent,
+            center.y + extent,
+        );
+        let full = sweep.abs() >= TAU;
+        /* A negative sweep is the same arc drawn from its other end. */
+        let (start, sweep) = if sweep < 0. {
+            (start + sweep, -sweep)
+        } else {
+            (start, sweep)
+        };
+        let end = start + sweep;
+        let ray = |angle: f32| Point::new(angle.cos(), angle.sin());
+        let (start_ray, end_ray) = (ray(start), ray(end));
+        self.shade(paint, bounds, |p| {
+            let p = p - center;
+            let distance = p.length();
+            match style {
+                Style::Fill => {
+                    let disc = (radius - distance + 0.5).clamp(0., 1.);
+                    if full {
+                        return disc;
+                    }
+                    /* Inside of each edge of the slice, as seen from the slice. */
+                    let after_start = (p.y * start_ray.x - p.x * start_ray.y + 0.5).clamp(0., 1.);
+                    let before_end = (p.x * end_ray.y - p.y * end_ray.x + 0.5).clamp(0., 1.);
+                    let wedge = if sweep <= TAU / 2. {
+                        after_start.min(before_end)
+                    } else {
+                        after_start.max(before_end)
+                    };
+                    disc.min(wedge)
+                }
+                Style::Stroke { width } => {
+                    let along = (p.y.atan2(p.x) - start).rem_euclid(TAU);
+                    let distance = if full || along <= sweep {
+                        (distance - radius).abs()
+                    } else {
+                        let to_start = (p - scale(start_ray, radius)).length();
+                        let to_end = (p - scale(end_ray, radius)).length();
+                        to_start.min(to_end)
+                    };
+                    stroke_coverage(distance, width)
+                }
+            }
+        });
+    }
+
+    /// Fill the polygon through `points`, which is closed from the last point back to the first.
+    pub fn polygon(&mut self, paint: Paint, points: &[Point], rule: FillRule) {
+        if points.len() < 3 {
+            return;
+        }
+        let edges: Vec<(Point, Point)> = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(&a, &b)| (a, b))
+            .filter(|(a, b)| a.y != b.y)
+            .collect();
+        let min_y = points.iter().map(|p| p.y).fold(f32::INFINITY, f32::min);
+        let max_y = points.iter().map(|p| p.y).fold(f32::NEG_INFINITY, f32::max);
+        let rows = (min_y.floor().max(0.) as i32)..(max_y.ceil().min(HEIGHT as f32) as i32);
+
+        let mut coverage = vec![0_f32; WIDTH as usize];
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for y in rows {
+            coverage.fill(0.);
+            for sample in 0..POLYGON_SUBSAMPLES {
+                let sample_y = y as f32 + (sample as f32 + 0.5) / POLYGON_SUBSAMPLES as f32;
+                crossings.clear();
+                for &(a, b) in &edges {
+                    let (top, bottom, winding) = if a.y < b.y { (a, b, 1) } else { (b, a, -1) };
+                    if (top.y..bottom.y).contains(&sample_y) {
+                        let x =
+                            top.x + (sample_y - top.y) * (bottom.x - top.x) / (bottom.y - top.y);
+                        crossings.push((x, winding));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    let inside = match rule {
+                        FillRule::EvenOdd => winding % 2 != 0,
+                        FillRule::NonZero => winding != 0,
+                    };
+                    if inside {
+                        add_span(&mut coverage, pair[0].0, pair[1].0);
+                    }
+                }
+            }
+            for (x, &covered) in coverage.iter().enumerate() {
+                if covered > 0. {
+                    let covered = covered / POLYGON_SUBSAMPLES as f32;
+                    self.paint(paint, x as i32, y, level(covered));
+                }
+            }
+        }
+    }
+
+    /// Paint the pixels within `bounds` (left, top, right, bottom) that are on the screen, by the
+    /// coverage at their centers.
+    fn shade(
+        &mut self,
+        paint: Paint,
+        bounds: (f32, f32, f32, f32),
+        coverage: impl Fn(Point) -> f32,
+    ) {
+        let (left, top, right, bottom) = bounds;
+        let columns = (left.floor().max(0.) as i32)..(right.ceil().min(WIDTH as f32) as i32);
+        let rows = (top.floor().max(0.) as i32)..(bottom.ceil().min(HEIGHT as f32) as i32);
+        for y in rows {
+            for x in columns.clone() {
+                let covered = coverage(Point::new(x as f32 + 0.5, y as f32 + 0.5));
+                if covered > 0. {
+                    self.paint(paint, x, y, level(covered));
+                }
+            }
+        }
+    }
+}
+
+fn scale(p: Point, factor: f32) -> Point {
+    Point::new(p.x * factor, p.y * factor)
+}
+
+/// Distance from `p` to the segment from `a` to `b`.
+fn segment_distance(p: Point, a: Point, b: Point) -> f32 {
+    let ab = b - a;
+    let ap = p - a;
+    let length_squared = ab.x * ab.x + ab.y * ab.y;
+    let t = if length_squared > 0. {
+        ((ap.x * ab.x + ap.y * ab.y) / length_squared).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (ap - scale(ab, t)).length()
+}
+
+/// Coverage of a pixel whose center is `distance` from the middle of a stroke of `width`. Strokes
+/// thinner than a pixel are drawn a pixel wide and lighter.
+fn stroke_coverage(distance: f32, width: f32) -> f32 {
+    let drawn = width.max(1.);
+    (drawn / 2. - distance + 0.5).clamp(0., 1.) * (width / drawn)
+}
+
+/// Add the part of each pixel that `from..to` covers horizontally, clipped to the screen.
+fn add_span(coverage: &mut [f32], from: f32, to: f32) {
+    let from = from.clamp(0., WIDTH as f32);
+    let to = to.clamp(0., WIDTH as f32);
+    if to <= from {
+        return;
+    }
+    let columns = (from.floor() as usize)..(to.ceil() as usize);
+    for (covered, x) in coverage[columns.clone()].iter_mut().zip(columns) {
+        let left = from.max(x as f32);
+        let right = to.min(x as f32 + 1.);
+        *covered += right - left;
+    }
+}
+
+fn level(coverage: f32) -> u8 {
+    (coverage.clamp(0., 1.) * 255.).round() as u8
+}
@@ -0,0 +1,126 @@
+//! What a boot does with the partitions before and after the clock face is drawn. Shared by the
+//! firmware and the simulator, which only differ in how the face gets onto the display.
+
+use chrono::NaiveDateTime;
+
+use crate::{
+    burst,
+    counter::{count_pulse, read_counter, repair_counter, Corruption},
+    datetime::datetime_from_counter,
+    event_log::{log_event, Event},
+    face,
+    flash::FlashStorage,
+    profile::{record_timing, BootTiming},
+    pulse::PowerMode,
+    reset::{is_pulse_boot, ResetReason},
+};
+
+/// How the board came to boot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Boot {
+    pub reset_reason: ResetReason,
+    /// Whether the boot counts as a minute pulse.
+    pub pulse_boot: bool,
+    /// Whether the boot is part of a burst of catch-up pulses, so drawing should wait.
+    pub catching_up: bool,
+}
+
+/// What the counter held after a boot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Counted {
+    pub value: u32,
+    /// Corruption that was found and rewritten before counting.
+    pub repair: Option<Corruption>,
+    /// Whether a missed pulse was detected and added.
+    pub corrected: bool,
+}
+
+impl Boot {
+    /// Tell what kind of boot this is, see [`is_pulse_boot`]. A pulse boot reads `log` to tell
+    /// whether a burst is going on, see [`burst::catching_up`].
+    pub fn classify<F: FlashStorage>(
+        log: &F,
+        reset_reason: ResetReason,
+        usb_powered: Option<bool>,
+        power_mode: PowerMode,
+        min_rapid_boots: u32,
+        max_held_boots: u32,
+    ) -> Result<Boot, F::Error> {
+        let pulse_boot =
+            power_mode != PowerMode::Continuous && is_pulse_boot(reset_reason, usb_powered);
+        let catching_up = pulse_boot && burst::catching_up(log, min_rapid_boots, max_held_boots)?;
+        Ok(Boot {
+            reset_reason,
+            pulse_boot,
+            catching_up,
+        })
+    }
+
+    /// Repair the counter, count the pulse of a pulse boot and log the boot. `polarity` is the
+    /// level of the pulse line as for [`count_pulse`].
+    pub fn count<F: FlashStorage>(
+        &self,
+        counter: &mut F,
+        log: &mut F,
+        polarity: Option<bool>,
+    ) -> Result<Counted, F::Error> {
+        let repair = repair_counter(counter)?;
+        let (value, corrected) = if self.pulse_boot {
+            count_pulse(counter, polarity)?
+        } else {
+            (read_counter(counter)?, false)
+        };
+        let event = Event::Boot {
+            reset_reason: self.reset_reason,
+            counter: value,
+            pulse_boot: self.pulse_boot,
+            repaired: repair.is_some(),
+            corrected,
+            catching_up: self.catching_up,
+        };
+        log_event(log, &event)?;
+        Ok(Counted {
+            value,
+            repair,
+            corrected,
+        })
+    }
+
+    /// Log that the clock face is on the display, which tells the next boots this one was no
+    /// catch-up pulse. The timing of a pulse boot is kept for the worst case, including the wait
+    /// for a burst to settle, which has to fit in a pulse as well. Without `timing`, as in the
+    /// simulator, nothing is kept.
+    pub fn drawn<F: FlashStorage>(
+        &self,
+        log: &mut F,
+        profile: &mut F,
+        timing: Option<&BootTiming>,
+    ) -> Result<(), F::Error> {
+        let event = Event::Drawn {
+            total_us: timing.map_or(0, |timing| timing.total_us),
+        };
+        log_event(log, &event)?;
+        match timing {
+            Some(timing) if self.pulse_boot => record_timing(profile, timing),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Counted {
+    pub fn time(&self) -> NaiveDateTime {
+        datetime_from_counter(self.value)
+    }
+
+    /// Status badges for the clock face.
+    pub fn badges(&self) -> Vec<&'static str> {
+        [
+            (self.repair.is_some(), face::REPAIRED_BADGE),
+            (self.corrected, face::CORRECTED_BADGE),
+        ]
+        .into_iter()
+        .filter(|(shown, _)| *shown)
+        .map(|(_, text)| text)
+        .collect()
+    }
+}
@@ -1,16 +1,35 @@
-use std::{thread::sleep, time::Duration};
+use std::{
+    io::BufRead,
+    ptr,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
 
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use embedded_hal::digital::v2::InputPin;
 use esp_idf_hal::{
     cpu::Core,
-    gpio::{GpioPin, Input},
+    gpio::{GpioPin, Input, Pin},
+};
+use esp_idf_sys::{esp_vfs_dev_uart_use_driver, uart_driver_install, EspError};
+
+use paperslave_core::{
+    adjust_flash::{apply_effect, console_event, count_running_pulse, start_model},
+    adjust_model::{AdjustModel, Button, Effect as ModelEffect, Event as ModelEvent, Screen},
+    adjust_view::draw_adjust,
+    buttons::{ButtonEvent, ButtonTiming, Buttons},
+    config,
+    console::{self, Partitions},
+    face::draw_clock_face,
+    fb::Framebuffer,
+    prepared::{DrawMode, PreparedFramebuffer},
+    pulse::counts_edges,
 };
 
 use crate::{
-    fb::{Framebuffer, Paint, Rect},
-    paper::{DrawMode, Paper, PreparedFramebuffer},
-    thread, clamp_datetime_to_counter, read_counter, datetime_from_counter, find_counter_partition, counter_from_datetime, set_counter,
+    paper::Paper,
+    partition::EspPartition,
+    pulse_input::PulseInput,
+    thread,
+    wake::{self, Wake},
 };
 
 pub struct AdjustButtons {
@@ -19,32 +38,81 @@ pub struct AdjustButtons {
     pub forward: GpioPin<Input>,
 }
 
+/// Adjust mode state shared between the input, console and draw threads.
+struct Shared {
+    model: Mutex<AdjustModel>,
+    /// What the draw thread should show.
+    frame: Mutex<Frame>,
+    frame_changed: Condvar,
+    /// Wakes the input loop.
+    wake: Wake,
+    partitions: Mutex<Partitions<EspPartition>>,
+}
+
 #[derive(Clone, PartialEq)]
-struct State {
-    field: AdjustField,
-    time: NaiveDateTime,
-    changed: bool,
+struct Frame {
+    model: AdjustModel,
+    /// Incremented to request a full redraw instead of a difference update.
+    full_redraw: u32,
+    /// Power down after drawing.
+    sleep: bool,
 }
 
-pub fn adjust_mode(mut paper: Paper, buttons: AdjustButtons) {
-    let partition = find_counter_partition();
-    let state = std::sync::Arc::new(std::sync::Mutex::new(State {
-        field: AdjustField::Years,
-        time: datetime_from_counter(read_counter(&partition)),
-        changed: false,
-    }));
+/// Let the user adjust the stored time. `shown` is what is on the display when this is called.
+/// While the board counts pulses from `pulses`, the clock face is shown until a button is pressed.
+pub fn adjust_mode(
+    mut paper: Paper,
+    buttons: AdjustButtons,
+    shown: Framebuffer,
+    mut pulses: Option<PulseInput>,
+) {
+    let mut partitions = Partitions {
+        counter: EspPartition::find("counter"),
+        profile: EspPartition::find("profile"),
+        log: EspPartition::find("log"),
+        saves: EspPartition::find("saves"),
+    };
+    #[cfg(debug_assertions)]
+    println!(
+        "counter sector erase counts: {:?}",
+        paperslave_core::counter::erase_counts(&partitions.counter).unwrap()
+    );
+    let counting_edges = pulses.is_some() && counts_edges(config::POWER_MODE, false);
+    let model = start_model(&mut partitions, counting_edges).unwrap();
+    let shared = Arc::new(Shared {
+        frame: Mutex::new(Frame {
+            model: model.clone(),
+            full_redraw: 0,
+            sleep: false,
+        }),
+        frame_changed: Condvar::new(),
+        wake: Wake::new(),
+        model: Mutex::new(model),
+        partitions: Mutex::new(partitions),
+    });
+
+    // Serial console
+    let console_shared = shared.clone();
+    thread::spawn(Core::Core0, move || console_loop(&console_shared));
 
     // Draw thread
-    let worker_state = state.clone();
+    let worker_shared = shared.clone();
+    let wake_pins = [buttons.field_cycle.pin(), buttons.forward.pin()];
     thread::spawn(Core::Core1, move || {
-        paper.powered_on().clear();
         let mut framebuffer = Framebuffer::new();
-        let mut prev_framebuffer = Framebuffer::new();
-        let mut local_state = worker_state.lock().unwrap().clone();
-        let mut dirty = false;
-        'redraw: loop {
+        let mut prev_framebuffer = shown;
+        let mut frame = worker_shared.frame.lock().unwrap().clone();
+        /* The clock face is updated from what is on the display already. */
+        if frame.model.screen == Screen::Adjust {
+            paper.powered_on().clear();
+            prev_framebuffer.clear();
+        }
+        loop {
             framebuffer.clear();
-            draw(&mut framebuffer, &local_state);
+            match frame.model.screen {
+                Screen::Clock => draw_clock_face(&mut framebuffer, frame.model.clock, &[]),
+                Screen::Adjust => draw_adjust(&mut framebuffer, &frame.model),
+            }
             let prepared = PreparedFramebuffer::prepare_difference(
                 &prev_framebuffer,
                 &framebuffer,
@@ -52,206 +120,149 @@ pub fn adjust_mode(mut paper: Paper, buttons: AdjustButtons) {
             );
             paper.powered_on().draw(&prepared);
             std::mem::swap(&mut prev_framebuffer, &mut framebuffer);
-            let mut tries = 0;
-            loop {
-                sleep(Duration::from_millis(10));
-                {
-                    let updated_state = worker_state.lock().unwrap();
-                    if &*updated_state != &local_state {
-                        local_state = updated_state.clone();
-                        dirty = true;
-                        continue 'redraw;
-                    }
-                }
-                if dirty && local_state.changed {
-                    tries += 1;
-                    if tries >= 500 {
-                        paper.powered_on().quick_clear();
-                        prev_framebuffer.clear();
-                        dirty = false;
-                        continue 'redraw;
-                    }
-                }
+            if frame.sleep {
+                drop(paper);
+                println!("adjust mode timed out, powering down");
+                wake::deep_sleep_until_low(wake_pins);
             }
+            let shown_frame = worker_shared.frame.lock().unwrap();
+            let updated_frame = worker_shared
+                .frame_changed
+                .wait_while(shown_frame, |updated| *updated == frame)
+                .unwrap()
+                .clone();
+            if updated_frame.full_redraw != frame.full_redraw {
+                paper.powered_on().quick_clear();
+                prev_framebuffer.clear();
+            }
+            frame = updated_frame;
         }
     });
 
     // Input loop
+    for pin in [&buttons.field_cycle, &buttons.backward, &buttons.forward] {
+        shared.wake.watch_pin(pin.pin());
+    }
+    if let Some(pulses) = &pulses {
+        pulses.wake(shared.wake);
+    }
+    let mut buttons = Buttons::new(
+        ButtonTiming::DEFAULT,
+        vec![
+            (Button::Cycle, buttons.field_cycle),
+            (Button::Backward, buttons.backward),
+            (Button::Forward, buttons.forward),
+        ],
+    );
+    let started = Instant::now();
+    let mut ticked_ms = 0;
     loop {
-        press_latch(&buttons.field_cycle, false, || {
-            let mut state = state.lock().unwrap();
-            state.field = state.field.cycle();
-        });
-        press_latch(&buttons.backward, true, || {
-            let mut state = state.lock().unwrap();
-            state.time = adjust(state.field, AdjustDirection::Backward, state.time);
-            state.changed = true;
-        });
-        press_latch(&buttons.forward, true, || {
-            let mut state = state.lock().unwrap();
-            if matches!(state.field, AdjustField::Store) {
-                set_counter(&partition, counter_from_datetime(state.time));
-                state.changed = false;
-            } else {
-                state.time = adjust(state.field, AdjustDirection::Forward, state.time);
-                state.changed = true;
+        if let Some(pulses) = &mut pulses {
+            for _ in 0..pulses.poll() {
+                let event = count_running_pulse(&mut shared.partitions.lock().unwrap()).unwrap();
+                let counting_edges = shared.model.lock().unwrap().counting_edges;
+                dispatch(&shared, event);
+                if !counting_edges && shared.model.lock().unwrap().counting_edges {
+                    println!("pulse seen while running, counting pulses from the input");
+                }
             }
-        });
-        sleep(Duration::from_millis(10));
-    }
-}
-
-fn draw(framebuffer: &mut Framebuffer, state: &State) {
-    const BUTTONS_START: i32 = 240;
-    const BUTTONS_SPACE: i32 = 69;
-    for (i, text) in [Some("RST"), None, Some("NEXT"), Some("-"), Some("+")]
-        .into_iter()
-        .enumerate()
-    {
-        if let Some(text) = text {
-            let pos = BUTTONS_START + i as i32 * BUTTONS_SPACE;
-            framebuffer.rect(
-                Paint::Darken,
-                Rect {
-                    x: pos - 20,
-                    y: 0,
-                    w: 40,
-                    h: 4,
-                },
-            );
-            framebuffer.text_centered(Paint::Darken, pos, 30, 30., text);
         }
-    }
-    for (part, x, y) in [
-        (AdjustField::Hours, 400, 180),
-        (AdjustField::Minutes, 560, 180),
-        (AdjustField::Days, 290, 300),
-        (AdjustField::Months, 450, 300),
-        (AdjustField::Years, 650, 300),
-        (AdjustField::Store, 480, 430),
-    ] {
-        framebuffer.text_centered(Paint::Darken, x, y, 94., &part.format(state.time));
-        if part == state.field {
-            framebuffer.rect(
-                Paint::Darken,
-                Rect {
-                    x: x - 40,
-                    y: y + 10,
-                    w: 80,
-                    h: 6,
-                },
-            );
+        let now_ms = started.elapsed().as_millis() as u64;
+        for event in buttons.poll(now_ms).unwrap() {
+            let event = match event {
+                ButtonEvent::Down(button) => ModelEvent::Press(button),
+                ButtonEvent::Repeat(button) => ModelEvent::Repeat(button),
+                ButtonEvent::LongPress(button) => ModelEvent::LongPress(button),
+                _ => continue,
+            };
+            dispatch(&shared, event);
         }
-    }
-    if state.changed {
-        framebuffer.text_centered(Paint::Darken, 480, 480, 50., "not saved");
+        let elapsed_ms = (now_ms - ticked_ms) as u32;
+        ticked_ms = now_ms;
+        dispatch(&shared, ModelEvent::Tick { elapsed_ms });
+
+        /* Sleep until an edge on the inputs, or until a button or the model has something to
+         * do. */
+        let model_due_ms = shared.model.lock().unwrap().next_tick_ms();
+        let buttons_due_ms = buttons
+            .next_deadline_ms()
+            .map(|deadline_ms| deadline_ms.saturating_sub(now_ms));
+        let timeout_ms = [model_due_ms.map(u64::from), buttons_due_ms]
+            .into_iter()
+            .flatten()
+            .min();
+        shared.wake.wait(timeout_ms.map(Duration::from_millis));
     }
 }
 
-fn press_latch(pin: &GpioPin<Input>, repeat: bool, mut cb: impl FnMut()) {
-    if pin.is_low().unwrap() {
-        cb();
-        for _ in 0..50 {
-            if pin.is_high().unwrap() {
-                return;
+/// Feed `event` to the model and carry out the effects.
+fn dispatch(shared: &Shared, event: ModelEvent) {
+    let effects = {
+        let mut model = shared.model.lock().unwrap();
+        let effects = model.handle(event);
+        /* Still under the model lock, so a frame is never replaced by an older one. */
+        let mut frame = shared.frame.lock().unwrap();
+        for effect in &effects {
+            match effect {
+                ModelEffect::Redraw => frame.model = model.clone(),
+                ModelEffect::FullRedraw => {
+                    frame.model = model.clone();
+                    frame.full_redraw = frame.full_redraw.wrapping_add(1);
+                }
+                ModelEffect::Sleep => frame.sleep = true,
+                _ => {}
             }
-            sleep(Duration::from_millis(10));
         }
-        loop {
-            if pin.is_high().unwrap() {
-                return;
-            }
-            if repeat {
-                cb();
-                sleep(Duration::from_millis(200));
-            } else {
-                sleep(Duration::from_millis(10));
-            }
+        shared.frame_changed.notify_all();
+        effects
+    };
+    for effect in effects {
+        let event = apply_effect(&mut shared.partitions.lock().unwrap(), effect).unwrap();
+        if let Some(event) = event {
+            dispatch(shared, event);
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
-enum AdjustField {
-    Years,
-    Months,
-    Days,
-    Hours,
-    Minutes,
-    Store,
-}
-
-#[derive(Clone, Copy)]
-enum AdjustDirection {
-    Forward,
-    Backward,
-}
-
-impl AdjustField {
-    fn cycle(self) -> Self {
-        match self {
-            AdjustField::Years => AdjustField::Months,
-            AdjustField::Months => AdjustField::Days,
-            AdjustField::Days => AdjustField::Hours,
-            AdjustField::Hours => AdjustField::Minutes,
-            AdjustField::Minutes => AdjustField::Store,
-            AdjustField::Store => AdjustField::Years,
-        }
+/// Read commands from the USB UART and feed their effects to the adjust model.
+fn console_loop(shared: &Shared) {
+    /* Without the driver, reads from stdin don't block and return nothing. */
+    unsafe {
+        EspError::convert(uart_driver_install(0, 256, 0, 0, ptr::null_mut(), 0)).unwrap();
+        esp_vfs_dev_uart_use_driver(0);
     }
-
-    fn format(self, datetime: NaiveDateTime) -> String {
-        let format_string = match self {
-            AdjustField::Years => "%Y",
-            AdjustField::Months => "%m",
-            AdjustField::Days => "%d",
-            AdjustField::Hours => "%H",
-            AdjustField::Minutes => "%M",
-            AdjustField::Store => "Save",
+    for line in std::io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => continue,
         };
-        datetime.format(format_string).to_string()
-    }
-}
-
-fn adjust(
-    field: AdjustField,
-    direction: AdjustDirection,
-    datetime: NaiveDateTime,
-) -> NaiveDateTime {
-    let mut date = datetime.date();
-    let mut time = datetime.time();
-    let mut overflow_days = 0;
-
-    let adjust_time = match direction {
-        AdjustDirection::Forward => NaiveTime::overflowing_add_signed,
-        AdjustDirection::Backward => NaiveTime::overflowing_sub_signed,
-    };
-    let adjust_date_duration = |date: NaiveDate, duration| {
-        match direction {
-            AdjustDirection::Forward => date.checked_add_signed(duration),
-            AdjustDirection::Backward => date.checked_sub_signed(duration),
-        }
-        .unwrap_or(date)
-    };
-    let adjust_date_months = |date: NaiveDate, months| {
-        match direction {
-            AdjustDirection::Forward => date.checked_add_months(months),
-            AdjustDirection::Backward => date.checked_sub_months(months),
-        }
-        .unwrap_or(date)
-    };
-
-    match field {
-        AdjustField::Minutes => {
-            (time, overflow_days) = adjust_time(&time, chrono::Duration::minutes(1))
-        }
-        AdjustField::Hours => {
-            (time, overflow_days) = adjust_time(&time, chrono::Duration::hours(1))
+        let command = match console::parse(&line) {
+            Ok(command) => command,
+            Err(error) => {
+                println!("error: {error}");
+                continue;
+            }
+        };
+        let save_timing = shared.model.lock().unwrap().save_timing();
+        let outcome = {
+            let mut partitions = shared.partitions.lock().unwrap();
+            console::execute(&mut partitions, command, save_timing).map(|outcome| {
+                let event = outcome
+                    .effect
+                    .map(|effect| console_event(&partitions, effect));
+                (outcome.reply, event)
+            })
+        };
+        match outcome {
+            Ok((reply, event)) => {
+                println!("{reply}");
+                if let Some(event) = event {
+                    dispatch(shared, event.unwrap());
+                }
+                /* The model may need ticks it didn't need before. */
+                shared.wake.wake();
+            }
+            Err(error) => println!("error: {error}"),
         }
-        AdjustField::Days => date = adjust_date_duration(date, chrono::Duration::days(1)),
-        AdjustField::Months => date = adjust_date_months(date, chrono::Months::new(1)),
-        AdjustField::Years => date = adjust_date_months(date, chrono::Months::new(12)),
-        AdjustField::Store => {}
     }
-    date = adjust_date_duration(date, chrono::Duration::seconds(overflow_days));
-    clamp_datetime_to_counter(date.and_time(time))
 }
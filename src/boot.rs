@@ -0,0 +1,49 @@
+//! What the hardware tells about this boot, for deciding whether it was caused by a minute pulse.
+
+use esp_idf_sys::{
+    esp_reset_reason, esp_reset_reason_t_ESP_RST_BROWNOUT, esp_reset_reason_t_ESP_RST_DEEPSLEEP,
+    esp_reset_reason_t_ESP_RST_EXT, esp_reset_reason_t_ESP_RST_INT_WDT,
+    esp_reset_reason_t_ESP_RST_PANIC, esp_reset_reason_t_ESP_RST_POWERON,
+    esp_reset_reason_t_ESP_RST_SDIO, esp_reset_reason_t_ESP_RST_SW,
+    esp_reset_reason_t_ESP_RST_TASK_WDT, esp_reset_reason_t_ESP_RST_WDT, gpio_get_level,
+    gpio_mode_t_GPIO_MODE_INPUT, gpio_reset_pin, gpio_set_direction,
+};
+
+use paperslave_core::{config, reset::ResetReason};
+
+/// Why the chip was last reset.
+#[allow(non_upper_case_globals)]
+pub fn reset_reason() -> ResetReason {
+    match unsafe { esp_reset_reason() } {
+        esp_reset_reason_t_ESP_RST_POWERON => ResetReason::PowerOn,
+        esp_reset_reason_t_ESP_RST_EXT => ResetReason::External,
+        esp_reset_reason_t_ESP_RST_SW => ResetReason::Software,
+        esp_reset_reason_t_ESP_RST_PANIC => ResetReason::Panic,
+        esp_reset_reason_t_ESP_RST_INT_WDT => ResetReason::InterruptWatchdog,
+        esp_reset_reason_t_ESP_RST_TASK_WDT => ResetReason::TaskWatchdog,
+        esp_reset_reason_t_ESP_RST_WDT => ResetReason::OtherWatchdog,
+        esp_reset_reason_t_ESP_RST_DEEPSLEEP => ResetReason::DeepSleep,
+        esp_reset_reason_t_ESP_RST_BROWNOUT => ResetReason::Brownout,
+        esp_reset_reason_t_ESP_RST_SDIO => ResetReason::Sdio,
+        _ => ResetReason::Unknown,
+    }
+}
+
+/// Whether the board is powered from USB, if the board has a way to tell.
+pub fn usb_powered() -> Option<bool> {
+    config::VBUS_SENSE_GPIO.map(read_pin)
+}
+
+/// Polarity of the pulse powering the board, if it is wired up.
+pub fn pulse_polarity() -> Option<bool> {
+    config::POLARITY_SENSE_GPIO.map(read_pin)
+}
+
+/// Read a pin not claimed through the HAL.
+fn read_pin(pin: i32) -> bool {
+    unsafe {
+        gpio_reset_pin(pin);
+        gpio_set_direction(pin, gpio_mode_t_GPIO_MODE_INPUT);
+        gpio_get_level(pin) != 0
+    }
+}
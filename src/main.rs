@@ -1,21 +1,34 @@
-use std::{ffi::{c_void, CString}, thread::sleep, time::Duration};
+use std::{thread::sleep, time::Duration};
 
-use chrono::NaiveDateTime;
 use esp_idf_hal::{cpu::Core, peripherals::Peripherals};
-use esp_idf_sys::{
-    self as _, esp_partition_erase_range, esp_partition_find_first, esp_partition_read,
-    esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY, esp_partition_t,
-    esp_partition_type_t_ESP_PARTITION_TYPE_ANY, esp_partition_write, EspError,
+use esp_idf_sys::esp_timer_get_time;
+
+use paperslave_core::{
+    config,
+    face::draw_clock_face,
+    fb::Framebuffer,
+    prepared::{DrawMode, PreparedFramebuffer},
+    profile::BootTiming,
+    pulse::PowerMode,
+    reset::ResetReason,
+    startup::Boot,
 };
 
 pub mod paper;
-use paper::{DrawMode, Paper, PaperPeripherals, PreparedFramebuffer};
-
-pub mod fb;
-use fb::{Framebuffer, Paint};
+use paper::{Paper, PaperPeripherals};
 
 pub mod thread;
 
+pub mod partition;
+use partition::EspPartition;
+
+pub mod boot;
+
+pub mod pulse_input;
+use pulse_input::PulseInput;
+
+pub mod wake;
+
 pub mod adjust;
 use crate::adjust::{adjust_mode, AdjustButtons};
 
@@ -52,30 +65,83 @@ fn main() {
         println!("wait over");
     }
 
-    let draw_worker = thread::spawn(Core::Core1, || {
-        let counter = find_counter_partition();
-        let value = read_and_increment_counter(&counter);
-        let time = datetime_from_counter(value);
-
-        let time_string = time.format("%H:%M").to_string();
-        let date_string = time.format("%-d.%-m.%Y").to_string();
+    let reset_reason = boot::reset_reason();
+    let polarity = boot::pulse_polarity();
+    /* Started early so that a pulse arriving while the face is drawn is not lost. */
+    let pulses = match config::PULSE_INPUT_GPIO {
+        Some(pin) if config::POWER_MODE != PowerMode::PulsePowered => Some(PulseInput::start(pin)),
+        _ => None,
+    };
+    let this_boot = Boot::classify(
+        &EspPartition::find("log"),
+        reset_reason,
+        boot::usb_powered(),
+        config::POWER_MODE,
+        config::BURST_MIN_RAPID_BOOTS,
+        config::BURST_MAX_HELD_BOOTS,
+    )
+    .unwrap();
+    #[cfg(debug_assertions)]
+    println!("{this_boot:?}");
+
+    let draw_worker = thread::spawn(Core::Core1, move || {
+        let mut timing = BootTiming::default();
+        let start = timer_us();
+        let mut counter = EspPartition::find("counter");
+        let mut log = EspPartition::find("log");
+        let counted = this_boot.count(&mut counter, &mut log, polarity).unwrap();
+        if let Some(corruption) = counted.repair {
+            println!("counter was corrupted and has been rewritten: {corruption:?}");
+        }
+        if counted.corrected {
+            println!("pulse polarity does not match, corrected a missed pulse");
+        }
+        timing.counter_us = elapsed_us(start);
 
+        let start = timer_us();
         let mut framebuffer = Framebuffer::new();
-        framebuffer.text_centered(Paint::Darken, fb::WIDTH / 2, 96, 90., "Aikamme");
-        framebuffer.text_centered(Paint::Darken, fb::WIDTH / 2, 405, 454., &time_string);
-        framebuffer.text_centered(Paint::Darken, fb::WIDTH / 2, 500, 90., &date_string);
-        PreparedFramebuffer::prepare(&framebuffer, DrawMode::DirectUpdateBinary)
+        draw_clock_face(&mut framebuffer, counted.time(), &counted.badges());
+        timing.render_us = elapsed_us(start);
+
+        let start = timer_us();
+        let prepared = PreparedFramebuffer::prepare(&framebuffer, DrawMode::DirectUpdateBinary);
+        timing.prepare_us = elapsed_us(start);
+        (framebuffer, prepared, timing)
     });
 
-    {
+    /* The draw worker increments the counter meanwhile. If the next catch-up pulse cuts the power
+     * during the wait, nothing has been spent on a render that could not have finished. */
+    if this_boot.catching_up {
+        sleep(Duration::from_millis(config::BURST_SETTLE_MS));
+    }
+
+    let shown = {
         let mut p = paper.powered_on();
+        let start = timer_us();
         p.quick_clear();
-        let prepared = draw_worker.join().unwrap();
+        let quick_clear_us = elapsed_us(start);
+        let (framebuffer, prepared, mut timing) = draw_worker.join().unwrap();
+        let start = timer_us();
         p.draw(&prepared);
+        timing.quick_clear_us = quick_clear_us;
+        timing.draw_us = elapsed_us(start);
+        timing.total_us = elapsed_us(0);
+        println!("boot timing: {timing}");
+        this_boot
+            .drawn(
+                &mut EspPartition::find("log"),
+                &mut EspPartition::find("profile"),
+                Some(&timing),
+            )
+            .unwrap();
+        framebuffer
+    };
+
+    /* Woken up by a button, which has nothing to wait for. */
+    if reset_reason != ResetReason::DeepSleep {
+        sleep(Duration::from_millis(3000));
     }
 
-    sleep(Duration::from_millis(3000));
-
     #[cfg(debug_assertions)]
     println!("entering adjust mode");
 
@@ -86,126 +152,16 @@ fn main() {
             backward: pins.gpio34.into_input().unwrap().degrade(),
             forward: pins.gpio39.into_input().unwrap().degrade(),
         },
+        shown,
+        pulses,
     );
 }
 
-fn datetime_from_counter(counter: u32) -> NaiveDateTime {
-    let minutes: i64 = counter.into();
-    NaiveDateTime::from_timestamp(60 * minutes, 0)
-}
-
-fn counter_from_datetime(datetime: NaiveDateTime) -> u32 {
-    let minutes = datetime.timestamp() / 60;
-    if minutes >= 0 {
-        minutes.try_into().unwrap_or(u32::MAX)
-    } else {
-        0
-    }
-}
-
-fn clamp_datetime_to_counter(datetime: NaiveDateTime) -> NaiveDateTime {
-    datetime_from_counter(counter_from_datetime(datetime))
+fn timer_us() -> i64 {
+    unsafe { esp_timer_get_time() }
 }
 
-fn find_counter_partition() -> esp_partition_t {
-    let partition_name = CString::new("counter").unwrap();
-    unsafe {
-        return *esp_partition_find_first(
-            esp_partition_type_t_ESP_PARTITION_TYPE_ANY,
-            esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY,
-            partition_name.as_ptr(),
-        );
-    }
-}
-
-fn read_counter(partition: &esp_partition_t) -> u32 {
-    read_and_increment_counter_impl(partition, false)
-}
-
-fn read_and_increment_counter(partition: &esp_partition_t) -> u32 {
-    read_and_increment_counter_impl(partition, true)
-}
-
-/// Monotonically increasing counter implementation which minimizes the use of the expensive flash
-/// erase operation.
-fn read_and_increment_counter_impl(partition: &esp_partition_t, increment: bool) -> u32 {
-    let size = partition.size;
-
-    let mut buffer = vec![0_u8; size as usize];
-    partition_read(partition, 0, &mut buffer).unwrap();
-
-    /* First 4 bytes of the counter are a base value. */
-    let base = u32::from_be_bytes(buffer[0..4].try_into().unwrap());
-
-    /* The rest is an offset that will be added to the base value. The offset is implemented as a
-     * unary counter where every increment flips one bit from 1 to 0. When all the bits are fully
-     * flipped, the offset region is reset and the base value is updated. */
-    let offset_region = &buffer[4..];
-    let unary_head_index = offset_region.partition_point(|&x| x == 0);
-    let unary_bits_head_byte;
-
-    match offset_region.get(unary_head_index) {
-        /* Increase the offset unary counter by one bit. */
-        Some(&head_byte) => {
-            unary_bits_head_byte = head_byte.leading_zeros();
-
-            if increment {
-                let new_head_byte = head_byte >> 1;
-                let byte_index = 4 + unary_head_index as u32;
-                partition_write(partition, byte_index, &new_head_byte.to_be_bytes()).unwrap();
-            }
-        }
-        /* The offset unary counter is already full, reset it and update base. */
-        None => {
-            unary_bits_head_byte = 0;
-
-            if increment {
-                let new_base = base + (size - 4) * 8 + 1;
-                set_counter(partition, new_base);
-            }
-        }
-    }
-
-    let offset = unary_head_index as u32 * 8 + unary_bits_head_byte;
-    return base + offset;
-}
-
-/// Overwrite counter in a way that **does not** conserve flash erase cycles.
-///
-/// Should not be used repeatedly.
-fn set_counter(partition: &esp_partition_t, value: u32) {
-    partition_erase(partition, 0, partition.size).unwrap();
-    partition_write(partition, 0, &value.to_be_bytes()).unwrap();
-}
-
-fn partition_erase(partition: &esp_partition_t, offset: u32, size: u32) -> Result<(), EspError> {
-    unsafe {
-        return EspError::convert(esp_partition_erase_range(partition, offset, size));
-    }
-}
-
-fn partition_write(partition: &esp_partition_t, offset: u32, data: &[u8]) -> Result<(), EspError> {
-    unsafe {
-        return EspError::convert(esp_partition_write(
-            partition,
-            offset,
-            data.as_ptr() as *const c_void,
-            data.len() as u32,
-        ));
-    }
-}
-
-fn partition_read(
-    partition: &esp_partition_t,
-    offset: u32,
-    buffer: &mut [u8],
-) -> Result<(), EspError> {
-    unsafe {
-        return EspError::convert(esp_partition_read(
-            partition,
-            offset,
-            buffer.as_mut_ptr() as *mut c_void,
-            buffer.len() as u32,
-        ));
-    }
+/// Microseconds since `start`, a reading of [`timer_us`].
+fn elapsed_us(start: i64) -> u32 {
+    (timer_us() - start) as u32
 }
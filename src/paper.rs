@@ -2,16 +2,16 @@ use esp_idf_hal::{gpio::*, rmt};
 use esp_idf_sys::{
     epd_clear, epd_clear_area, epd_clear_area_cycles, epd_deinit, epd_draw_base, epd_full_screen,
     epd_init, epd_poweroff, epd_poweron, epd_set_rotation, epdiy_ED047TC1,
-    EpdDrawError_EPD_DRAW_SUCCESS, EpdDrawMode, EpdDrawMode_MODE_DU,
-    EpdDrawMode_MODE_EPDIY_BLACK_TO_GL16, EpdDrawMode_MODE_EPDIY_WHITE_TO_GL16,
-    EpdDrawMode_MODE_GC16, EpdDrawMode_MODE_GL16, EpdDrawMode_MODE_PACKING_1PPB_DIFFERENCE,
-    EpdDrawMode_MODE_PACKING_2PPB, EpdDrawMode_PREVIOUSLY_WHITE,
-    EpdInitOptions_EPD_OPTIONS_DEFAULT, EpdRotation_EPD_ROT_LANDSCAPE,
+    EpdDrawError_EPD_DRAW_SUCCESS, EpdDrawMode_MODE_DU, EpdDrawMode_MODE_EPDIY_BLACK_TO_GL16,
+    EpdDrawMode_MODE_EPDIY_WHITE_TO_GL16, EpdDrawMode_MODE_GC16, EpdDrawMode_MODE_GL16,
+    EpdDrawMode_MODE_PACKING_1PPB_DIFFERENCE, EpdDrawMode_MODE_PACKING_2PPB,
+    EpdDrawMode_PREVIOUSLY_WHITE, EpdInitOptions_EPD_OPTIONS_DEFAULT,
+    EpdRotation_EPD_ROT_LANDSCAPE,
 };
 
 pub use esp_idf_sys::EpdRect;
 
-use crate::fb::{Framebuffer, HEIGHT, WIDTH};
+use paperslave_core::prepared::{DrawMode, Packing, PreparedFramebuffer};
 
 pub struct PaperPeripherals {
     pub gpio0: Gpio0<Unknown>,
@@ -62,21 +62,6 @@ impl Drop for Paper {
 
 pub struct PaperPowerOn<'a>(&'a mut Paper);
 
-#[derive(Clone, Copy)]
-#[repr(u32)]
-pub enum DrawMode {
-    DirectUpdateBinary = EpdDrawMode_MODE_DU,
-    Flashing = EpdDrawMode_MODE_GC16,
-    NonFlashing = EpdDrawMode_MODE_GL16,
-    FromWhiteQuick = EpdDrawMode_MODE_EPDIY_WHITE_TO_GL16,
-    FromBlackQuick = EpdDrawMode_MODE_EPDIY_BLACK_TO_GL16,
-}
-
-pub struct PreparedFramebuffer {
-    packed: Vec<u8>,
-    mode: EpdDrawMode,
-}
-
 impl<'a> PaperPowerOn<'a> {
     pub fn clear(&mut self) {
         unsafe {
@@ -97,12 +82,23 @@ impl<'a> PaperPowerOn<'a> {
     }
 
     pub fn draw(&mut self, prepared: &PreparedFramebuffer) {
+        let draw_mode = match prepared.draw_mode() {
+            DrawMode::DirectUpdateBinary => EpdDrawMode_MODE_DU,
+            DrawMode::Flashing => EpdDrawMode_MODE_GC16,
+            DrawMode::NonFlashing => EpdDrawMode_MODE_GL16,
+            DrawMode::FromWhiteQuick => EpdDrawMode_MODE_EPDIY_WHITE_TO_GL16,
+            DrawMode::FromBlackQuick => EpdDrawMode_MODE_EPDIY_BLACK_TO_GL16,
+        };
+        let packing = match prepared.packing() {
+            Packing::TwoPerByte => EpdDrawMode_PREVIOUSLY_WHITE | EpdDrawMode_MODE_PACKING_2PPB,
+            Packing::Difference => EpdDrawMode_MODE_PACKING_1PPB_DIFFERENCE,
+        };
         unsafe {
             let ret = epd_draw_base(
                 epd_full_screen(),
-                prepared.packed.as_ptr(),
+                prepared.packed().as_ptr(),
                 epd_full_screen(),
-                prepared.mode,
+                draw_mode | packing,
                 24,
                 core::ptr::null(),
                 &epdiy_ED047TC1 as *const _,
@@ -121,57 +117,3 @@ impl<'a> Drop for PaperPowerOn<'a> {
         }
     }
 }
-
-impl PreparedFramebuffer {
-    pub fn prepare(framebuffer: &Framebuffer, draw_mode: DrawMode) -> PreparedFramebuffer {
-        // TODO: use 8 bits per byte for binary mode?
-        assert!(WIDTH % 2 == 0);
-        let mut packed = vec![0; (WIDTH / 2 * HEIGHT) as usize];
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH / 2 {
-                let packed_idx = (y * (WIDTH / 2) + x) as usize;
-                let l = framebuffer.get(2 * x, y);
-                let r = framebuffer.get(2 * x + 1, y);
-                let (l, r) = if matches!(draw_mode, DrawMode::DirectUpdateBinary) {
-                    (15 * (l >> 7), 15 * (r >> 7))
-                } else {
-                    (l >> 4, r >> 4)
-                };
-                let combined = r << 4 | l;
-                packed[packed_idx] = combined;
-            }
-        }
-        PreparedFramebuffer {
-            packed,
-            mode: draw_mode as EpdDrawMode
-                | EpdDrawMode_PREVIOUSLY_WHITE
-                | EpdDrawMode_MODE_PACKING_2PPB,
-        }
-    }
-
-    pub fn prepare_difference(
-        from_framebuffer: &Framebuffer,
-        to_framebuffer: &Framebuffer,
-        draw_mode: DrawMode,
-    ) -> PreparedFramebuffer {
-        let mut packed = vec![0; (WIDTH * HEIGHT) as usize];
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                let packed_idx = (y * WIDTH + x) as usize;
-                let from = from_framebuffer.get(x, y);
-                let to = to_framebuffer.get(x, y);
-                let (from, to) = if matches!(draw_mode, DrawMode::DirectUpdateBinary) {
-                    (15 * (from >> 7), 15 * (to >> 7))
-                } else {
-                    (from >> 4, to >> 4)
-                };
-                let combined = to << 4 | from;
-                packed[packed_idx] = combined;
-            }
-        }
-        PreparedFramebuffer {
-            packed,
-            mode: draw_mode as EpdDrawMode | EpdDrawMode_MODE_PACKING_1PPB_DIFFERENCE,
-        }
-    }
-}
@@ -0,0 +1,69 @@
+use std::ffi::{c_void, CString};
+
+use esp_idf_sys::{
+    esp_partition_erase_range, esp_partition_find_first, esp_partition_read,
+    esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY, esp_partition_t,
+    esp_partition_type_t_ESP_PARTITION_TYPE_ANY, esp_partition_write, EspError,
+};
+
+use paperslave_core::flash::FlashStorage;
+
+/// Raw data partition from the partition table.
+pub struct EspPartition(esp_partition_t);
+
+/* The partition API locks the flash internally, and the pointers in `esp_partition_t` refer to
+ * the static partition table. */
+unsafe impl Send for EspPartition {}
+
+impl EspPartition {
+    pub fn find(name: &str) -> EspPartition {
+        let partition_name = CString::new(name).unwrap();
+        unsafe {
+            let partition = esp_partition_find_first(
+                esp_partition_type_t_ESP_PARTITION_TYPE_ANY,
+                esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY,
+                partition_name.as_ptr(),
+            );
+            if partition.is_null() {
+                panic!("partition {name} not found");
+            }
+            return EspPartition(*partition);
+        }
+    }
+}
+
+impl FlashStorage for EspPartition {
+    type Error = EspError;
+
+    fn size(&self) -> u32 {
+        self.0.size
+    }
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), EspError> {
+        unsafe {
+            return EspError::convert(esp_partition_read(
+                &self.0,
+                offset,
+                buffer.as_mut_ptr() as *mut c_void,
+                buffer.len() as u32,
+            ));
+        }
+    }
+
+    fn write(&mut self, offset: u32, data: &[u8]) -> Result<(), EspError> {
+        unsafe {
+            return EspError::convert(esp_partition_write(
+                &self.0,
+                offset,
+                data.as_ptr() as *const c_void,
+                data.len() as u32,
+            ));
+        }
+    }
+
+    fn erase(&mut self, offset: u32, size: u32) -> Result<(), EspError> {
+        unsafe {
+            return EspError::convert(esp_partition_erase_range(&self.0, offset, size));
+        }
+    }
+}
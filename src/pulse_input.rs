@@ -0,0 +1,116 @@
+//! Interrupt-driven sampling of the pulse input.
+
+use std::{
+    ffi::c_void,
+    mem, ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use esp_idf_sys::{
+    esp_timer_get_time, gpio_get_level, gpio_install_isr_service, gpio_int_type_t_GPIO_INTR_ANYEDGE,
+    gpio_intr_enable, gpio_isr_handler_add, gpio_mode_t_GPIO_MODE_INPUT, gpio_reset_pin,
+    gpio_set_direction, gpio_set_intr_type, xQueueGenericCreate, xQueueGenericSendFromISR,
+    xQueueReceive, EspError, QueueHandle_t, ESP_ERR_INVALID_STATE,
+};
+
+use paperslave_core::{config, pulse::PulseDetector};
+
+use crate::wake::Wake;
+
+const QUEUE_LENGTH: u32 = 32;
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct Edge {
+    time_us: i64,
+    level: i32,
+}
+
+/// Parameter of the interrupt handler, leaked since the handler is never removed.
+struct IsrContext {
+    queue: QueueHandle_t,
+    pin: i32,
+    /// [`Wake`] to post to on every edge, if any.
+    wake: AtomicPtr<c_void>,
+}
+
+pub struct PulseInput {
+    queue: QueueHandle_t,
+    context: &'static IsrContext,
+    detector: PulseDetector,
+}
+
+/* The queue handle is only used through the thread-safe FreeRTOS queue API. */
+unsafe impl Send for PulseInput {}
+
+impl PulseInput {
+    /// Start recording edges of `pin` from an interrupt.
+    pub fn start(pin: i32) -> PulseInput {
+        /* Half a pulse interval is longer than any bounce and shorter than the next pulse. */
+        let holdoff_us = i64::from(config::PULSE_PROTOCOL.increment) * 1_000_000 / 2;
+        let detector = PulseDetector::new(config::PULSE_MIN_LENGTH_US, holdoff_us);
+        unsafe {
+            let queue = xQueueGenericCreate(QUEUE_LENGTH, mem::size_of::<Edge>() as u32, 0);
+            assert!(!queue.is_null(), "creating pulse edge queue failed");
+
+            gpio_reset_pin(pin);
+            gpio_set_direction(pin, gpio_mode_t_GPIO_MODE_INPUT);
+            gpio_set_intr_type(pin, gpio_int_type_t_GPIO_INTR_ANYEDGE);
+            /* The service may have been installed already for another pin. */
+            let result = gpio_install_isr_service(0);
+            if result != ESP_ERR_INVALID_STATE as i32 {
+                EspError::convert(result).unwrap();
+            }
+            let context = Box::leak(Box::new(IsrContext {
+                queue,
+                pin,
+                wake: AtomicPtr::new(ptr::null_mut()),
+            }));
+            let arg = context as *mut IsrContext as *mut c_void;
+            EspError::convert(gpio_isr_handler_add(pin, Some(edge_isr), arg)).unwrap();
+            gpio_intr_enable(pin);
+
+            PulseInput {
+                queue,
+                context,
+                detector,
+            }
+        }
+    }
+
+    /// Also post to `wake` on every edge.
+    pub fn wake(&self, wake: Wake) {
+        self.context.wake.store(wake.into_raw(), Ordering::Release);
+    }
+
+    /// Process the edges recorded so far. Returns the number of pulses they completed.
+    pub fn poll(&mut self) -> u32 {
+        let mut pulses = 0;
+        let mut edge = Edge::default();
+        while unsafe { xQueueReceive(self.queue, &mut edge as *mut Edge as *mut c_void, 0) } == 1 {
+            if self.detector.edge(edge.time_us, edge.level != 0) {
+                pulses += 1;
+            }
+        }
+        pulses
+    }
+}
+
+unsafe extern "C" fn edge_isr(arg: *mut c_void) {
+    let context = &*(arg as *const IsrContext);
+    let edge = Edge {
+        time_us: esp_timer_get_time(),
+        level: gpio_get_level(context.pin),
+    };
+    /* A full queue drops the edge. Polled at every wake-up, it never fills up. */
+    xQueueGenericSendFromISR(
+        context.queue,
+        &edge as *const Edge as *const c_void,
+        ptr::null_mut(),
+        0,
+    );
+    let wake = context.wake.load(Ordering::Acquire);
+    if !wake.is_null() {
+        Wake::from_raw(wake).wake_from_isr();
+    }
+}
@@ -0,0 +1,116 @@
+//! Waking the input loop of adjust mode from pin interrupts, so that it can block instead of
+//! polling, and waking from deep sleep.
+
+use std::{ffi::c_void, mem, ptr, time::Duration};
+
+use esp_idf_sys::{
+    esp_deep_sleep_start, esp_sleep_enable_ext0_wakeup, esp_sleep_enable_ext1_wakeup,
+    esp_sleep_ext1_wakeup_mode_t_ESP_EXT1_WAKEUP_ALL_LOW, gpio_install_isr_service,
+    gpio_int_type_t_GPIO_INTR_ANYEDGE, gpio_intr_enable, gpio_isr_handler_add, gpio_set_intr_type,
+    xQueueGenericCreate, xQueueGenericSend, xQueueGenericSendFromISR, xQueueReceive, EspError,
+    QueueHandle_t, TickType_t, CONFIG_FREERTOS_HZ, ESP_ERR_INVALID_STATE,
+};
+
+const QUEUE_LENGTH: u32 = 8;
+
+/// A queue that interrupts and other threads post to, to wake up whoever waits on it.
+#[derive(Clone, Copy)]
+pub struct Wake {
+    queue: QueueHandle_t,
+}
+
+/* The queue handle is only used through the thread-safe FreeRTOS queue API. */
+unsafe impl Send for Wake {}
+unsafe impl Sync for Wake {}
+
+impl Wake {
+    pub fn new() -> Wake {
+        let queue = unsafe { xQueueGenericCreate(QUEUE_LENGTH, mem::size_of::<u8>() as u32, 0) };
+        assert!(!queue.is_null(), "creating wake queue failed");
+        Wake { queue }
+    }
+
+    /// Wake up on every edge of `pin`, which has to be configured as an input already.
+    pub fn watch_pin(self, pin: i32) {
+        unsafe {
+            gpio_set_intr_type(pin, gpio_int_type_t_GPIO_INTR_ANYEDGE);
+            /* The service may have been installed already for another pin. */
+            let result = gpio_install_isr_service(0);
+            if result != ESP_ERR_INVALID_STATE as i32 {
+                EspError::convert(result).unwrap();
+            }
+            EspError::convert(gpio_isr_handler_add(pin, Some(wake_isr), self.into_raw())).unwrap();
+            gpio_intr_enable(pin);
+        }
+    }
+
+    pub fn wake(self) {
+        /* A full queue wakes the waiter anyway. */
+        unsafe { xQueueGenericSend(self.queue, &0_u8 as *const u8 as *const c_void, 0, 0) };
+    }
+
+    /// Wake from an interrupt handler.
+    ///
+    /// # Safety
+    /// Only to be called from an interrupt handler.
+    pub unsafe fn wake_from_isr(self) {
+        xQueueGenericSendFromISR(
+            self.queue,
+            &0_u8 as *const u8 as *const c_void,
+            ptr::null_mut(),
+            0,
+        );
+    }
+
+    /// Block until woken, or at most `timeout` if given. Wake-ups that came in before this call
+    /// return right away.
+    pub fn wait(self, timeout: Option<Duration>) {
+        let ticks = match timeout {
+            Some(timeout) => {
+                let ticks = timeout.as_millis() as u64 * u64::from(CONFIG_FREERTOS_HZ) / 1000;
+                /* One tick more, as the current tick may be almost over. */
+                (ticks + 1).min(TickType_t::MAX as u64 - 1) as TickType_t
+            }
+            None => TickType_t::MAX,
+        };
+        let mut item = 0_u8;
+        let item = &mut item as *mut u8 as *mut c_void;
+        if unsafe { xQueueReceive(self.queue, item, ticks) } == 1 {
+            /* Several wake-ups since the last wait are handled as one. */
+            while unsafe { xQueueReceive(self.queue, item, 0) } == 1 {}
+        }
+    }
+
+    /// The queue as a pointer, e.g. for storing in an interrupt handler's parameter.
+    pub fn into_raw(self) -> *mut c_void {
+        self.queue as *mut c_void
+    }
+
+    /// # Safety
+    /// `raw` has to come from [`Wake::into_raw`].
+    pub unsafe fn from_raw(raw: *mut c_void) -> Wake {
+        Wake {
+            queue: raw as QueueHandle_t,
+        }
+    }
+}
+
+/// Power down until one of `pins` reads low, which restarts the app.
+///
+/// The ESP32 can only wake on a low level from one pin through ext0, and from several pins only
+/// once all of them are low through ext1, so just two pins can wake it up.
+pub fn deep_sleep_until_low(pins: [i32; 2]) -> ! {
+    unsafe {
+        EspError::convert(esp_sleep_enable_ext0_wakeup(pins[0], 0)).unwrap();
+        EspError::convert(esp_sleep_enable_ext1_wakeup(
+            1 << pins[1],
+            esp_sleep_ext1_wakeup_mode_t_ESP_EXT1_WAKEUP_ALL_LOW,
+        ))
+        .unwrap();
+        esp_deep_sleep_start()
+    }
+}
+
+unsafe extern "C" fn wake_isr(arg: *mut c_void) {
+    Wake::from_raw(arg).wake_from_isr();
+}
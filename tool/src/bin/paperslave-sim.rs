@@ -0,0 +1,497 @@
+//! Headless simulator of the clock, running the firmware's counter, drawing and adjust mode logic
+//! against in-memory partitions.
+//!
+//! Reads a script from a file, or from stdin if none is given, and writes every update sent to the
+//! display as a numbered PNG into the output directory. Commands, one per line:
+//! - `load counter|log|profile|saves <dump>`: start from a partition dump
+//! - `time 2026-10-17T12:34`: store a time in the counter, like `paperslave-tool encode`
+//! - `boot [short] [high|low]`: pulse boot, after which the pulse is over and the power goes away.
+//!   A `short` pulse is over before anything is drawn, like the catch-up pulses of a burst. With
+//!   `high` or `low`, the pulse line has that level for the polarity check.
+//! - `start`: boot on steady power, which enters adjust mode. Without `VBUS_SENSE_GPIO`, it counts
+//!   a pulse like plugging in the board does.
+//! - `press next|-|+ [ms]`: hold a button for 100 ms or the given time, then let go for 100 ms
+//! - `wait <ms>`: let time pass
+//! - `pulse`: pulse seen on the pulse input while running
+//! - `console <command>`: serial console command, e.g. `console add 5`
+//!
+//! Pressing NEXT or + after adjust mode has powered down wakes the board up like on the device.
+//! Lines starting with `#` are comments. Boot timing depends on the hardware and is not simulated.
+
+use std::{
+    cell::Cell,
+    convert::Infallible,
+    env, fs,
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
+    process::ExitCode,
+    rc::Rc,
+};
+
+use chrono::NaiveDateTime;
+use embedded_hal::digital::v2::InputPin;
+
+#[allow(dead_code)]
+#[path = "../image.rs"]
+mod image;
+#[path = "../partition_table.rs"]
+mod partition_table;
+
+use image::write_png;
+use paperslave_core::{
+    adjust_flash::{apply_effect, console_event, count_running_pulse, start_model},
+    adjust_model::{AdjustModel, Button, Effect as ModelEffect, Event as ModelEvent, Screen},
+    adjust_view::draw_adjust,
+    buttons::{ButtonEvent, ButtonTiming, Buttons},
+    config,
+    console::{self, Partitions},
+    counter::set_counter,
+    datetime::counter_from_datetime,
+    face::draw_clock_face,
+    fb::Framebuffer,
+    flash::{SimulatedFlash, SimulatedFlashError, SECTOR_SIZE},
+    prepared::{DrawMode, PreparedFramebuffer},
+    pulse::counts_edges,
+    reset::ResetReason,
+    startup::Boot,
+};
+use partition_table::partition;
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// Interval of the simulated input loop.
+const STEP_MS: u64 = 10;
+
+/// How long `press` holds a button by default, and how long it lets go afterwards.
+const PRESS_MS: u64 = 100;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (output, script): (&str, Box<dyn BufRead>) = match args[..] {
+        [output] => (output, Box::new(io::stdin().lock())),
+        [output, script] => match fs::File::open(script) {
+            Ok(file) => (output, Box::new(BufReader::new(file))),
+            Err(error) => {
+                eprintln!("error: reading {script}: {error}");
+                return ExitCode::FAILURE;
+            }
+        },
+        _ => {
+            eprintln!("usage: paperslave-sim <output directory> [script]");
+            return ExitCode::from(2);
+        }
+    };
+    if let Err(error) = fs::create_dir_all(output) {
+        eprintln!("error: creating {output}: {error}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut sim = Simulator::new(PathBuf::from(output));
+    let mut failed = false;
+    for (number, line) in script.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                eprintln!("error: reading script: {error}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Err(error) = sim.run(line) {
+            eprintln!("line {}: {error}", number + 1);
+            failed = true;
+        }
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// A button pin whose level the script sets.
+struct SimPin {
+    pressed: Rc<Cell<bool>>,
+}
+
+impl InputPin for SimPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(!self.pressed.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(self.pressed.get())
+    }
+}
+
+/// The display, keeping what it shows for difference updates.
+struct Display {
+    output: PathBuf,
+    shown: Framebuffer,
+    frames: u32,
+}
+
+impl Display {
+    fn clear(&mut self) {
+        self.shown.clear();
+    }
+
+    fn draw(&mut self, framebuffer: &Framebuffer, difference: bool) -> Result<(), String> {
+        let prepared = if difference {
+            PreparedFramebuffer::prepare_difference(
+                &self.shown,
+                framebuffer,
+                DrawMode::DirectUpdateBinary,
+            )
+        } else {
+            PreparedFramebuffer::prepare(framebuffer, DrawMode::DirectUpdateBinary)
+        };
+        let decoded = prepared.decode();
+        let changed = decoded
+            .pixels()
+            .iter()
+            .zip(self.shown.pixels())
+            .filter(|(new, old)| new != old)
+            .count();
+        self.frames += 1;
+        let kind = if difference { "difference" } else { "full" };
+        let path = self.output.join(format!("{:04}-{kind}.png", self.frames));
+        write_png(&path, &decoded).map_err(|e| format!("writing {}: {e}", path.display()))?;
+        println!("{}: {changed} pixels changed", path.display());
+        self.shown = decoded;
+        Ok(())
+    }
+}
+
+/// Adjust mode while the board runs.
+struct Adjust {
+    model: AdjustModel,
+    buttons: Buttons<Button, SimPin>,
+    levels: Vec<(Button, Rc<Cell<bool>>)>,
+    now_ms: u64,
+}
+
+enum Power {
+    Off,
+    /// Powered down by adjust mode, until NEXT or + is pressed.
+    Asleep,
+    Running(Box<Adjust>),
+}
+
+struct Simulator {
+    partitions: Partitions<SimulatedFlash>,
+    display: Display,
+    power: Power,
+}
+
+impl Simulator {
+    fn new(output: PathBuf) -> Simulator {
+        let flash = |name| SimulatedFlash::new(partition(name).1 / SECTOR_SIZE);
+        Simulator {
+            partitions: Partitions {
+                counter: flash("counter"),
+                profile: flash("profile"),
+                log: flash("log"),
+                saves: flash("saves"),
+            },
+            display: Display {
+                output,
+                shown: Framebuffer::new(),
+                frames: 0,
+            },
+            power: Power::Off,
+        }
+    }
+
+    fn run(&mut self, line: &str) -> Result<(), String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            ["load", name, path] => self.load(name, path),
+            ["time", datetime] => {
+                let datetime = NaiveDateTime::parse_from_str(datetime, DATETIME_FORMAT)
+                    .map_err(|e| format!("parsing {datetime}: {e}"))?;
+                set_counter(
+                    &mut self.partitions.counter,
+                    counter_from_datetime(datetime),
+                )
+                .map_err(flash_error)
+            }
+            ["boot", ref options @ ..] => {
+                let mut short = false;
+                let mut polarity = None;
+                for option in options {
+                    match *option {
+                        "short" => short = true,
+                        "high" => polarity = Some(true),
+                        "low" => polarity = Some(false),
+                        _ => {
+                            return Err(format!("unknown option {option}, use short, high or low"))
+                        }
+                    }
+                }
+                self.boot(ResetReason::PowerOn, Some(false), polarity, short)?;
+                self.power = Power::Off;
+                Ok(())
+            }
+            ["start"] => {
+                /* Plugged into USB, which the board can only tell from a pulse by sensing VBUS. */
+                let usb_powered = config::VBUS_SENSE_GPIO.map(|_| true);
+                self.boot(ResetReason::PowerOn, usb_powered, None, false)?;
+                self.enter_adjust()
+            }
+            ["press", button] => self.press(button, PRESS_MS),
+            ["press", button, ms] => {
+                let ms = ms.parse().map_err(|_| format!("bad duration {ms}"))?;
+                self.press(button, ms)
+            }
+            ["wait", ms] => {
+                let ms = ms.parse().map_err(|_| format!("bad duration {ms}"))?;
+                self.wait(ms)
+            }
+            ["pulse"] => self.pulse(),
+            ["console", ..] => self.console(line.trim_start_matches("console").trim()),
+            _ => Err(format!("unknown command {line}")),
+        }
+    }
+
+    fn load(&mut self, name: &str, path: &str) -> Result<(), String> {
+        let image = fs::read(path).map_err(|e| format!("reading {path}: {e}"))?;
+        let size = partition(name).1;
+        if image.len() as u32 != size {
+            return Err(format!(
+                "{path} is {} bytes, the {name} partition {size}",
+                image.len()
+            ));
+        }
+        let flash = match name {
+            "counter" => &mut self.partitions.counter,
+            "profile" => &mut self.partitions.profile,
+            "log" => &mut self.partitions.log,
+            "saves" => &mut self.partitions.saves,
+            _ => return Err(format!("no partition {name} to load")),
+        };
+        *flash = SimulatedFlash::from_image(image);
+        Ok(())
+    }
+
+    /// Start up like `main` of the firmware: count the pulse and draw the clock face, unless the
+    /// pulse is `short` and the power goes away first.
+    fn boot(
+        &mut self,
+        reset_reason: ResetReason,
+        usb_powered: Option<bool>,
+        polarity: Option<bool>,
+        short: bool,
+    ) -> Result<(), String> {
+        let Partitions { counter, log, .. } = &mut self.partitions;
+        let boot = Boot::classify(
+            log,
+            reset_reason,
+            usb_powered,
+            config::POWER_MODE,
+            config::BURST_MIN_RAPID_BOOTS,
+            config::BURST_MAX_HELD_BOOTS,
+        )
+        .map_err(flash_error)?;
+        let counted = boot.count(counter, log, polarity).map_err(flash_error)?;
+        if let Some(corruption) = counted.repair {
+            println!("counter was corrupted and has been rewritten: {corruption:?}");
+        }
+        if counted.corrected {
+            println!("pulse polarity does not match, corrected a missed pulse");
+        }
+        println!("boot at {}", counted.time().format(DATETIME_FORMAT));
+        if short {
+            println!("power gone before drawing");
+            return Ok(());
+        }
+        if boot.catching_up {
+            println!("catching up, drawing after {} ms", config::BURST_SETTLE_MS);
+        }
+
+        let mut framebuffer = Framebuffer::new();
+        draw_clock_face(&mut framebuffer, counted.time(), &counted.badges());
+        self.display.clear();
+        self.display.draw(&framebuffer, false)?;
+        let Partitions { profile, log, .. } = &mut self.partitions;
+        boot.drawn(log, profile, None).map_err(flash_error)
+    }
+
+    /// Go on to adjust mode after a boot, once the power has stayed on.
+    fn enter_adjust(&mut self) -> Result<(), String> {
+        /* As if the board has a pulse input, so that pulses can be simulated. */
+        let counting_edges = counts_edges(config::POWER_MODE, false);
+        let model = start_model(&mut self.partitions, counting_edges).map_err(flash_error)?;
+        let levels: Vec<(Button, Rc<Cell<bool>>)> =
+            [Button::Cycle, Button::Backward, Button::Forward]
+                .into_iter()
+                .map(|button| (button, Rc::new(Cell::new(false))))
+                .collect();
+        let pins = levels
+            .iter()
+            .map(|(button, pressed)| {
+                let pressed = pressed.clone();
+                (*button, SimPin { pressed })
+            })
+            .collect();
+        let buttons = Buttons::new(ButtonTiming::DEFAULT, pins);
+        if model.screen == Screen::Adjust {
+            self.display.clear();
+            self.draw(&model)?;
+        }
+        self.power = Power::Running(Box::new(Adjust {
+            model,
+            buttons,
+            levels,
+            now_ms: 0,
+        }));
+        Ok(())
+    }
+
+    fn press(&mut self, name: &str, duration_ms: u64) -> Result<(), String> {
+        let button = match name {
+            "next" => Button::Cycle,
+            "-" => Button::Backward,
+            "+" => Button::Forward,
+            _ => return Err(format!("unknown button {name}, use next, - or +")),
+        };
+        match self.power {
+            Power::Running(_) => {}
+            Power::Asleep if button == Button::Backward => {
+                println!("asleep, only NEXT and + wake the board up");
+                return Ok(());
+            }
+            Power::Asleep => {
+                println!("waking up");
+                self.boot(ResetReason::DeepSleep, Some(false), None, false)?;
+                return self.enter_adjust();
+            }
+            Power::Off => return Err("the board is not running".to_string()),
+        }
+        self.set_level(button, true);
+        self.wait(duration_ms)?;
+        self.set_level(button, false);
+        self.wait(PRESS_MS)
+    }
+
+    fn set_level(&mut self, button: Button, pressed: bool) {
+        if let Power::Running(adjust) = &self.power {
+            for (pin_button, level) in &adjust.levels {
+                if *pin_button == button {
+                    level.set(pressed);
+                }
+            }
+        }
+    }
+
+    /// Run the input loop of adjust mode for `duration_ms`.
+    fn wait(&mut self, duration_ms: u64) -> Result<(), String> {
+        for _ in 0..duration_ms / STEP_MS {
+            let adjust = match &mut self.power {
+                Power::Running(adjust) => adjust,
+                _ => return Ok(()),
+            };
+            adjust.now_ms += STEP_MS;
+            let events: Vec<ModelEvent> = adjust
+                .buttons
+                .poll(adjust.now_ms)
+                .unwrap()
+                .into_iter()
+                .filter_map(|event| match event {
+                    ButtonEvent::Down(button) => Some(ModelEvent::Press(button)),
+                    ButtonEvent::Repeat(button) => Some(ModelEvent::Repeat(button)),
+                    ButtonEvent::LongPress(button) => Some(ModelEvent::LongPress(button)),
+                    _ => None,
+                })
+                .collect();
+            for event in events {
+                self.dispatch(event)?;
+            }
+            self.dispatch(ModelEvent::Tick {
+                elapsed_ms: STEP_MS as u32,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn pulse(&mut self) -> Result<(), String> {
+        if !matches!(self.power, Power::Running(_)) {
+            return Err("the board is not running, use boot for a pulse boot".to_string());
+        }
+        let event = count_running_pulse(&mut self.partitions).map_err(flash_error)?;
+        if let ModelEvent::Pulse { clock } = event {
+            println!("pulse, counter at {}", clock.format(DATETIME_FORMAT));
+        }
+        self.dispatch(event)
+    }
+
+    fn console(&mut self, line: &str) -> Result<(), String> {
+        let save_timing = match &self.power {
+            Power::Running(adjust) => adjust.model.save_timing(),
+            _ => return Err("the board is not running".to_string()),
+        };
+        let command = console::parse(line).map_err(|e| e.to_string())?;
+        let outcome =
+            console::execute(&mut self.partitions, command, save_timing).map_err(flash_error)?;
+        println!("{}", outcome.reply);
+        match outcome.effect {
+            Some(effect) => {
+                let event = console_event(&self.partitions, effect).map_err(flash_error)?;
+                self.dispatch(event)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Feed `event` to the model and carry out the effects, like `adjust::dispatch` of the firmware.
+    fn dispatch(&mut self, event: ModelEvent) -> Result<(), String> {
+        let adjust = match &mut self.power {
+            Power::Running(adjust) => adjust,
+            _ => return Ok(()),
+        };
+        let effects = adjust.model.handle(event);
+        let model = adjust.model.clone();
+        /* The draw thread shows the latest model once, however many redraws it was asked for. */
+        let full_redraw = effects.contains(&ModelEffect::FullRedraw);
+        if full_redraw || effects.contains(&ModelEffect::Redraw) {
+            if full_redraw {
+                self.display.clear();
+            }
+            self.draw(&model)?;
+        }
+        if effects.contains(&ModelEffect::Sleep) {
+            println!("adjust mode timed out, powering down");
+            self.power = Power::Asleep;
+        }
+        for effect in effects {
+            let event = apply_effect(&mut self.partitions, effect).map_err(flash_error)?;
+            if let Some(event) = event {
+                if let ModelEvent::Stored { time, .. } = &event {
+                    println!("stored {}", time.format(DATETIME_FORMAT));
+                }
+                self.dispatch(event)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw `model` like the draw thread of adjust mode, as a difference update.
+    fn draw(&mut self, model: &AdjustModel) -> Result<(), String> {
+        let mut framebuffer = Framebuffer::new();
+        match model.screen {
+            Screen::Clock => draw_clock_face(&mut framebuffer, model.clock, &[]),
+            Screen::Adjust => draw_adjust(&mut framebuffer, model),
+        }
+        self.display.draw(&framebuffer, true)
+    }
+}
+
+fn flash_error(error: SimulatedFlashError) -> String {
+    format!("{error:?}")
+}
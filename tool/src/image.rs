@@ -0,0 +1,44 @@
+//! Framebuffers as grayscale PNG images, for looking at what the firmware draws.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+};
+
+use paperslave_core::fb::{Framebuffer, HEIGHT, WIDTH};
+
+pub fn write_png(path: &Path, framebuffer: &Framebuffer) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        WIDTH as u32,
+        HEIGHT as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(framebuffer.pixels())?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Pixels of a PNG image written by [`write_png`], row by row like [`Framebuffer::pixels`].
+pub fn read_png(path: &Path) -> io::Result<Vec<u8>> {
+    let mut reader = png::Decoder::new(File::open(path)?).read_info()?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels)?;
+    if (info.width, info.height) != (WIDTH as u32, HEIGHT as u32)
+        || info.color_type != png::ColorType::Grayscale
+        || info.bit_depth != png::BitDepth::Eight
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is not an 8-bit grayscale image of the display",
+                path.display()
+            ),
+        ));
+    }
+    pixels.truncate(info.buffer_size());
+    Ok(pixels)
+}
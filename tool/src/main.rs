@@ -0,0 +1,136 @@
+//! Host tool for inspecting and preparing images of the `counter` and `log` partitions.
+//!
+//! The counter encoding comes from `paperslave-core`, the same code the firmware runs, so images
+//! produced here are read exactly the way the clock reads them.
+
+use std::{env, fs, process::ExitCode};
+
+use chrono::NaiveDateTime;
+
+mod partition_table;
+
+use paperslave_core::{
+    counter::{self, find_active_sector, read_counter, set_counter, OFFSET_CAPACITY},
+    datetime::{counter_from_datetime, datetime_from_counter},
+    event_log::{read_events, Event},
+    flash::{SimulatedFlash, SECTOR_SIZE},
+};
+use partition_table::partition;
+
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args[..] {
+        ["decode", path] => decode(path),
+        ["encode", datetime, path] => encode(datetime, path),
+        ["log", path] => log(path),
+        _ => {
+            eprintln!("usage:");
+            eprintln!("  paperslave-tool decode <partition dump>");
+            eprintln!("  paperslave-tool encode <YYYY-MM-DDTHH:MM> <output image>");
+            eprintln!("  paperslave-tool log <log partition dump>");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Read a partition dump, which has to consist of whole sectors.
+fn read_image(path: &str) -> Result<Vec<u8>, String> {
+    let image = fs::read(path).map_err(|e| format!("reading {path}: {e}"))?;
+    if image.is_empty() || image.len() as u32 % SECTOR_SIZE != 0 {
+        return Err(format!(
+            "image size {} is not a multiple of the sector size {SECTOR_SIZE}",
+            image.len()
+        ));
+    }
+    Ok(image)
+}
+
+fn decode(path: &str) -> Result<(), String> {
+    let mut image = read_image(path)?;
+    /* Dump of the old single-sector partition. The legacy counter is read from the last sector. */
+    if image.len() as u32 == SECTOR_SIZE {
+        image.splice(0..0, [0xFF; SECTOR_SIZE as usize]);
+    }
+    let mut flash = SimulatedFlash::from_image(image);
+
+    match find_active_sector(&flash).map_err(|e| format!("{e:?}"))? {
+        Some(active) => {
+            println!("active sector:  {}", active.index);
+            println!("sequence:       {}", active.header.sequence);
+            println!("base:           {}", active.header.base);
+            println!("offset:         {}", active.offset);
+            println!(
+                "remaining:      {} increments before the next erase",
+                OFFSET_CAPACITY.saturating_sub(active.offset)
+            );
+            println!("corrections:    {} missed pulses", active.corrections());
+            if let Some(phase) = active.polarity_phase {
+                println!("polarity phase: {}", phase as u8);
+            }
+            if let Some(corruption) = active.corruption {
+                println!("corruption:     {corruption:?}");
+            }
+        }
+        None => println!("no valid sector, reading as the legacy single-sector layout"),
+    }
+    let erase_counts = counter::erase_counts(&flash).map_err(|e| format!("{e:?}"))?;
+    println!("erase counts:   {erase_counts:?}");
+
+    let value = read_counter(&mut flash).map_err(|e| format!("{e:?}"))?;
+    println!("counter:        {value}");
+    println!(
+        "time:           {}",
+        datetime_from_counter(value).format(DATETIME_FORMAT)
+    );
+    Ok(())
+}
+
+fn encode(datetime: &str, path: &str) -> Result<(), String> {
+    let datetime = NaiveDateTime::parse_from_str(datetime, DATETIME_FORMAT)
+        .map_err(|e| format!("parsing {datetime}: {e}"))?;
+    let (partition_offset, partition_size) = partition("counter");
+
+    let mut flash = SimulatedFlash::new(partition_size / SECTOR_SIZE);
+    let value = counter_from_datetime(datetime);
+    set_counter(&mut flash, value).map_err(|e| format!("{e:?}"))?;
+    fs::write(path, flash.image()).map_err(|e| format!("writing {path}: {e}"))?;
+
+    println!(
+        "stored {} (counter {value}), shown as is at the next pulse",
+        datetime_from_counter(value).format(DATETIME_FORMAT)
+    );
+    println!("flash with: espflash write-bin {partition_offset:#x} {path}");
+    Ok(())
+}
+
+fn log(path: &str) -> Result<(), String> {
+    let flash = SimulatedFlash::from_image(read_image(path)?);
+    let events = read_events(&flash).map_err(|e| format!("{e:?}"))?;
+    if events.is_empty() {
+        let (partition_offset, partition_size) = partition("log");
+        println!("no events, check that the dump was read with:");
+        println!("espflash read-flash {partition_offset:#x} {partition_size:#x} {path}");
+    }
+    for event in events {
+        /* Counter values are easier to follow as times. */
+        let time = |value: u32| datetime_from_counter(value).format(DATETIME_FORMAT);
+        match event {
+            Event::Boot { counter, .. } | Event::Pulse { counter } => {
+                println!("{}  {event}", time(counter))
+            }
+            Event::Set { old, new, .. } => println!("{event} ({} -> {})", time(old), time(new)),
+            Event::Drawn { .. } => println!("{event}"),
+        }
+    }
+    Ok(())
+}
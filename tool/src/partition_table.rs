@@ -0,0 +1,18 @@
+//! The partition layout of the firmware, from `partitions.csv`.
+
+static PARTITION_TABLE: &str = include_str!("../../partitions.csv");
+
+/// Offset and size of partition `name` in `partitions.csv`.
+pub fn partition(name: &str) -> (u32, u32) {
+    let parse = |field: &str| {
+        let field = field.trim();
+        u32::from_str_radix(field.trim_start_matches("0x"), 16)
+            .unwrap_or_else(|_| panic!("bad number {field} in partitions.csv"))
+    };
+    PARTITION_TABLE
+        .lines()
+        .map(|line| line.split(',').collect::<Vec<_>>())
+        .find(|fields| fields[0].trim() == name)
+        .map(|fields| (parse(fields[3]), parse(fields[4])))
+        .unwrap_or_else(|| panic!("no {name} partition in partitions.csv"))
+}
@@ -0,0 +1,175 @@
+//! What adjust mode does with the partitions, shared by the firmware and the simulator.
+
+use chrono::TimeDelta;
+
+mod common;
+
+use common::at;
+use paperslave_core::{
+    adjust_flash::{apply_effect, console_event, count_running_pulse, start_model},
+    adjust_model::{Effect, Event, Screen},
+    console::{self, Partitions},
+    counter::{read_counter, set_counter},
+    datetime::{counter_from_datetime, SaveTiming},
+    event_log::{read_events, Event as LogEvent, SetSource},
+    flash::{FlashStorage, SimulatedFlash, SECTOR_SIZE},
+    profile::{record_timing, BootTiming},
+};
+
+fn partitions(datetime: &str) -> Partitions<SimulatedFlash> {
+    let mut partitions = Partitions {
+        counter: SimulatedFlash::new(4),
+        profile: SimulatedFlash::new(2),
+        log: SimulatedFlash::new(4),
+        saves: SimulatedFlash::new(2),
+    };
+    set_counter(&mut partitions.counter, counter_from_datetime(at(datetime))).unwrap();
+    partitions
+}
+
+fn save(datetime: &str) -> Effect {
+    Effect::Save {
+        time: at(datetime),
+        timing: SaveTiming::Now,
+    }
+}
+
+#[test]
+fn model_starts_from_the_partitions() {
+    let mut partitions = partitions("2026-10-17T12:34");
+    let timing = BootTiming {
+        total_us: 1_800_000,
+        ..BootTiming::default()
+    };
+    record_timing(&mut partitions.profile, &timing).unwrap();
+    apply_effect(&mut partitions, save("2026-10-17T13:00")).unwrap();
+
+    let model = start_model(&mut partitions, false).unwrap();
+    assert_eq!(model.time, at("2026-10-17T13:00"));
+    assert_eq!(model.screen, Screen::Adjust);
+    assert_eq!(model.worst_boot, Some(timing));
+    assert_eq!(
+        model.history,
+        [(at("2026-10-17T12:34"), at("2026-10-17T13:00"))]
+    );
+    assert_eq!(
+        start_model(&mut partitions, true).unwrap().screen,
+        Screen::Clock
+    );
+}
+
+#[test]
+fn running_pulse_is_counted_and_logged() {
+    let mut partitions = partitions("2026-10-17T12:34");
+    assert_eq!(
+        count_running_pulse(&mut partitions).unwrap(),
+        Event::Pulse {
+            clock: at("2026-10-17T12:34")
+        }
+    );
+    assert_eq!(
+        count_running_pulse(&mut partitions).unwrap(),
+        Event::Pulse {
+            clock: at("2026-10-17T12:35")
+        }
+    );
+    assert_eq!(
+        read_events(&partitions.log).unwrap().last(),
+        Some(&LogEvent::Pulse {
+            counter: counter_from_datetime(at("2026-10-17T12:35"))
+        })
+    );
+}
+
+#[test]
+fn save_and_undo_report_back_with_the_history() {
+    let mut partitions = partitions("2026-10-17T12:34");
+    assert_eq!(apply_effect(&mut partitions, Effect::Undo).unwrap(), None);
+
+    assert_eq!(
+        apply_effect(&mut partitions, save("2026-10-17T13:00")).unwrap(),
+        Some(Event::Stored {
+            time: at("2026-10-17T13:00"),
+            history: vec![(at("2026-10-17T12:34"), at("2026-10-17T13:00"))],
+        })
+    );
+    assert!(matches!(
+        read_events(&partitions.log).unwrap().last(),
+        Some(LogEvent::Set {
+            source: SetSource::Buttons,
+            ..
+        })
+    ));
+
+    assert_eq!(
+        apply_effect(&mut partitions, Effect::Undo).unwrap(),
+        Some(Event::Stored {
+            time: at("2026-10-17T12:34"),
+            history: vec![
+                (at("2026-10-17T12:34"), at("2026-10-17T13:00")),
+                (at("2026-10-17T13:00"), at("2026-10-17T12:34")),
+            ],
+        })
+    );
+    assert_eq!(
+        read_counter(&mut partitions.counter).unwrap(),
+        counter_from_datetime(at("2026-10-17T12:34"))
+    );
+}
+
+#[test]
+fn undo_outlasts_the_event_log() {
+    let mut partitions = partitions("2026-10-17T12:34");
+    apply_effect(&mut partitions, save("2026-10-17T13:00")).unwrap();
+    /* More pulses than the log holds, so the save has been overwritten there. */
+    let pulses = 1000;
+    assert!(pulses > partitions.log.size() / SECTOR_SIZE * 204);
+    for _ in 0..pulses {
+        count_running_pulse(&mut partitions).unwrap();
+    }
+    assert!(!read_events(&partitions.log)
+        .unwrap()
+        .iter()
+        .any(|event| matches!(event, LogEvent::Set { .. })));
+
+    let model = start_model(&mut partitions, false).unwrap();
+    assert_eq!(
+        model.history,
+        [(at("2026-10-17T12:34"), at("2026-10-17T13:00"))]
+    );
+    /* 12:34 was still waiting for its pulse when it was saved over. */
+    let restored = at("2026-10-17T12:34") + TimeDelta::minutes(pulses as i64 - 1);
+    assert!(matches!(
+        apply_effect(&mut partitions, Effect::Undo).unwrap(),
+        Some(Event::Stored { time, .. }) if time == restored
+    ));
+    assert_eq!(
+        read_counter(&mut partitions.counter).unwrap(),
+        counter_from_datetime(restored)
+    );
+}
+
+#[test]
+fn display_effects_leave_the_partitions_alone() {
+    let mut partitions = partitions("2026-10-17T12:34");
+    for effect in [Effect::Redraw, Effect::FullRedraw, Effect::Sleep] {
+        assert_eq!(apply_effect(&mut partitions, effect).unwrap(), None);
+    }
+    assert!(read_events(&partitions.log).unwrap().is_empty());
+}
+
+#[test]
+fn console_effects_become_model_events() {
+    let partitions = partitions("2026-10-17T12:34");
+    assert_eq!(
+        console_event(&partitions, console::Effect::Redraw).unwrap(),
+        Event::FullRedrawRequested
+    );
+    assert_eq!(
+        console_event(&partitions, console::Effect::Stored(at("2026-10-17T14:00"))).unwrap(),
+        Event::Stored {
+            time: at("2026-10-17T14:00"),
+            history: Vec::new(),
+        }
+    );
+}
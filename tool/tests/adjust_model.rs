@@ -0,0 +1,409 @@
+//! Scripted button sequences through the adjust mode model of the firmware.
+
+use chrono::NaiveDateTime;
+
+mod common;
+
+use common::at;
+use paperslave_core::{
+    adjust_model::{
+        AdjustField, AdjustModel, Button, Effect, Event, Screen, Settings, GHOST_CLEAR_MS,
+    },
+    datetime::SaveTiming,
+    pulse::PowerMode,
+};
+
+fn settings(power_mode: PowerMode) -> Settings {
+    Settings {
+        power_mode,
+        timeout_ms: 60_000,
+        save_on_timeout: false,
+    }
+}
+
+fn adjusting(datetime: &str) -> AdjustModel {
+    AdjustModel::new(
+        at(datetime),
+        false,
+        settings(PowerMode::PulsePowered),
+        None,
+        Vec::new(),
+    )
+}
+
+/// Feed `events` in order, returning the effects of the last one.
+fn feed(model: &mut AdjustModel, events: &[Event]) -> Vec<Effect> {
+    let mut effects = Vec::new();
+    for event in events {
+        effects = model.handle(event.clone());
+    }
+    effects
+}
+
+fn select(model: &mut AdjustModel, field: AdjustField) {
+    while model.field != field {
+        model.handle(Event::Press(Button::Cycle));
+    }
+}
+
+/// Step `field` of `datetime` once with `button`.
+fn step(datetime: &str, field: AdjustField, button: Button) -> NaiveDateTime {
+    let mut model = adjusting(datetime);
+    select(&mut model, field);
+    model.handle(Event::Press(button));
+    model.time
+}
+
+#[test]
+fn cycle_visits_every_field_and_wraps() {
+    let mut model = adjusting("2024-05-06T07:08");
+    let mut visited = vec![model.field];
+    for _ in 0..7 {
+        assert_eq!(model.handle(Event::Press(Button::Cycle)), [Effect::Redraw]);
+        visited.push(model.field);
+    }
+    /* The configured protocol counts whole minutes, so there is no seconds field. */
+    assert_eq!(
+        visited,
+        [
+            AdjustField::Years,
+            AdjustField::Months,
+            AdjustField::Days,
+            AdjustField::Hours,
+            AdjustField::Minutes,
+            AdjustField::Store,
+            AdjustField::Undo,
+            AdjustField::Years,
+        ]
+    );
+    assert!(!model.changed);
+}
+
+#[test]
+fn holding_cycle_does_not_repeat() {
+    let mut model = adjusting("2024-05-06T07:08");
+    model.handle(Event::Press(Button::Cycle));
+    let effects = feed(&mut model, &vec![Event::Repeat(Button::Cycle); 5]);
+    assert!(effects.is_empty());
+    assert_eq!(model.field, AdjustField::Months);
+}
+
+#[test]
+fn holding_a_step_button_repeats() {
+    let mut model = adjusting("2024-05-06T07:08");
+    select(&mut model, AdjustField::Minutes);
+    feed(
+        &mut model,
+        &[
+            Event::Press(Button::Forward),
+            Event::Repeat(Button::Forward),
+            Event::Repeat(Button::Forward),
+        ],
+    );
+    assert_eq!(model.time, at("2024-05-06T07:11"));
+    feed(
+        &mut model,
+        &[
+            Event::Press(Button::Backward),
+            Event::Repeat(Button::Backward),
+        ],
+    );
+    assert_eq!(model.time, at("2024-05-06T07:09"));
+    assert!(model.changed);
+    assert_eq!(model.clock, at("2024-05-06T07:08"));
+}
+
+#[test]
+fn time_of_day_rolls_over_into_the_date() {
+    assert_eq!(
+        step("1999-12-31T23:59", AdjustField::Minutes, Button::Forward),
+        at("2000-01-01T00:00")
+    );
+    assert_eq!(
+        step("2000-01-01T00:00", AdjustField::Minutes, Button::Backward),
+        at("1999-12-31T23:59")
+    );
+    assert_eq!(
+        step("2024-02-28T23:30", AdjustField::Hours, Button::Forward),
+        at("2024-02-29T00:30")
+    );
+    assert_eq!(
+        step("2024-03-01T00:30", AdjustField::Hours, Button::Backward),
+        at("2024-02-29T23:30")
+    );
+}
+
+#[test]
+fn dates_keep_to_the_end_of_shorter_months() {
+    assert_eq!(
+        step("2024-01-31T12:00", AdjustField::Months, Button::Forward),
+        at("2024-02-29T12:00")
+    );
+    assert_eq!(
+        step("2024-02-29T12:00", AdjustField::Years, Button::Forward),
+        at("2025-02-28T12:00")
+    );
+    assert_eq!(
+        step("2024-12-31T12:00", AdjustField::Days, Button::Forward),
+        at("2025-01-01T12:00")
+    );
+}
+
+#[test]
+fn time_stays_within_the_counter() {
+    assert_eq!(
+        step("1970-01-01T00:00", AdjustField::Minutes, Button::Backward),
+        at("1970-01-01T00:00")
+    );
+    assert_eq!(
+        step("1970-01-01T00:00", AdjustField::Years, Button::Backward),
+        at("1970-01-01T00:00")
+    );
+}
+
+#[test]
+fn save_is_requested_once_and_confirmed_by_stored() {
+    let mut model = adjusting("2024-05-06T07:08");
+    select(&mut model, AdjustField::Hours);
+    model.handle(Event::Press(Button::Forward));
+    select(&mut model, AdjustField::Store);
+
+    assert!(model.handle(Event::Press(Button::Backward)).is_empty());
+    assert_eq!(
+        model.handle(Event::Press(Button::Forward)),
+        [Effect::Save {
+            time: at("2024-05-06T08:08"),
+            timing: SaveTiming::NextPulse,
+        }]
+    );
+    assert!(feed(&mut model, &vec![Event::Repeat(Button::Forward); 3]).is_empty());
+    assert!(model.changed);
+
+    let history = vec![(at("2024-05-06T07:08"), at("2024-05-06T08:08"))];
+    let effects = model.handle(Event::Stored {
+        time: at("2024-05-06T08:08"),
+        history: history.clone(),
+    });
+    assert_eq!(effects, [Effect::Redraw]);
+    assert!(!model.changed);
+    assert_eq!(model.clock, at("2024-05-06T08:08"));
+    assert_eq!(model.history, history);
+    assert_eq!(model.screen, Screen::Adjust);
+}
+
+#[test]
+fn undo_is_requested_from_its_field() {
+    let mut model = adjusting("2024-05-06T07:08");
+    select(&mut model, AdjustField::Undo);
+    assert!(model.handle(Event::Press(Button::Backward)).is_empty());
+    assert_eq!(model.handle(Event::Press(Button::Forward)), [Effect::Undo]);
+    assert!(model.handle(Event::Repeat(Button::Forward)).is_empty());
+}
+
+#[test]
+fn clock_face_is_left_by_any_button_without_acting_on_it() {
+    let mut model = AdjustModel::new(
+        at("2024-05-06T07:08"),
+        true,
+        settings(PowerMode::Continuous),
+        None,
+        Vec::new(),
+    );
+    assert_eq!(model.screen, Screen::Clock);
+    assert_eq!(
+        model.handle(Event::Press(Button::Forward)),
+        [Effect::Redraw]
+    );
+    assert_eq!(model.screen, Screen::Adjust);
+    assert_eq!(model.time, at("2024-05-06T07:08"));
+    assert!(!model.changed);
+
+    select(&mut model, AdjustField::Store);
+    let effects = model.handle(Event::Press(Button::Forward));
+    assert_eq!(
+        effects,
+        [Effect::Save {
+            time: at("2024-05-06T07:08"),
+            timing: SaveTiming::Now,
+        }]
+    );
+    model.handle(Event::Stored {
+        time: at("2024-05-06T07:08"),
+        history: Vec::new(),
+    });
+    assert_eq!(model.screen, Screen::Clock);
+}
+
+#[test]
+fn pulses_advance_the_time_until_it_is_changed() {
+    let mut model = adjusting("2024-05-06T07:08");
+    select(&mut model, AdjustField::Minutes);
+    model.handle(Event::Pulse {
+        clock: at("2024-05-06T07:09"),
+    });
+    assert_eq!(model.time, at("2024-05-06T07:09"));
+
+    model.handle(Event::Press(Button::Backward));
+    model.handle(Event::Pulse {
+        clock: at("2024-05-06T07:10"),
+    });
+    assert_eq!(model.time, at("2024-05-06T07:08"));
+    assert_eq!(model.clock, at("2024-05-06T07:10"));
+    assert!(!model.counting_edges);
+    assert_eq!(model.screen, Screen::Adjust);
+}
+
+#[test]
+fn pulse_while_running_switches_auto_to_counting_edges() {
+    let mut model = AdjustModel::new(
+        at("2024-05-06T07:08"),
+        false,
+        settings(PowerMode::Auto),
+        None,
+        Vec::new(),
+    );
+    assert_eq!(model.save_timing(), SaveTiming::NextPulse);
+    model.handle(Event::Pulse {
+        clock: at("2024-05-06T07:09"),
+    });
+    assert!(model.counting_edges);
+    assert_eq!(model.screen, Screen::Clock);
+    assert_eq!(model.save_timing(), SaveTiming::Now);
+}
+
+#[test]
+fn ghosting_is_cleared_after_adjusting_stops() {
+    let mut model = adjusting("2024-05-06T07:08");
+    select(&mut model, AdjustField::Minutes);
+    model.handle(Event::Press(Button::Forward));
+    let tick = Event::Tick { elapsed_ms: 10 };
+    let ticks = (GHOST_CLEAR_MS / 10) as usize;
+    assert!(feed(&mut model, &vec![tick.clone(); ticks - 1]).is_empty());
+    assert_eq!(model.handle(tick.clone()), [Effect::FullRedraw]);
+    assert!(feed(&mut model, &vec![tick.clone(); ticks * 2]).is_empty());
+
+    /* Only cycling through the fields leaves little to clean up. */
+    let mut model = adjusting("2024-05-06T07:08");
+    model.handle(Event::Press(Button::Cycle));
+    assert!(feed(&mut model, &vec![tick; ticks * 2]).is_empty());
+}
+
+#[test]
+fn console_redraw_is_passed_on() {
+    let mut model = adjusting("2024-05-06T07:08");
+    assert_eq!(
+        model.handle(Event::FullRedrawRequested),
+        [Effect::FullRedraw]
+    );
+}
+
+#[test]
+fn long_press_on_the_field_button_skips_to_saving() {
+    let mut model = adjusting("2024-05-06T07:08");
+    feed(
+        &mut model,
+        &[
+            Event::Press(Button::Cycle),
+            Event::Repeat(Button::Cycle),
+            Event::LongPress(Button::Cycle),
+        ],
+    );
+    assert_eq!(model.field, AdjustField::Store);
+    assert!(model.handle(Event::LongPress(Button::Forward)).is_empty());
+}
+
+#[test]
+fn ticks_are_needed_for_the_next_ghost_clear_or_timeout() {
+    let mut model = adjusting("2024-05-06T07:08");
+    assert_eq!(model.next_tick_ms(), Some(60_000));
+    select(&mut model, AdjustField::Minutes);
+    model.handle(Event::Press(Button::Forward));
+    assert_eq!(model.next_tick_ms(), Some(GHOST_CLEAR_MS));
+    model.handle(Event::Tick { elapsed_ms: 1200 });
+    assert_eq!(model.next_tick_ms(), Some(GHOST_CLEAR_MS - 1200));
+    assert_eq!(
+        model.handle(Event::Tick {
+            elapsed_ms: GHOST_CLEAR_MS
+        }),
+        [Effect::FullRedraw]
+    );
+    assert_eq!(model.next_tick_ms(), Some(60_000 - 1200 - GHOST_CLEAR_MS));
+}
+
+#[test]
+fn timeout_discards_changes_and_powers_down() {
+    let mut model = adjusting("2024-05-06T07:08");
+    select(&mut model, AdjustField::Minutes);
+    model.handle(Event::Press(Button::Forward));
+    assert!(model.handle(Event::Tick { elapsed_ms: 59_000 }).len() <= 1);
+    /* A press restarts the wait. */
+    model.handle(Event::Press(Button::Forward));
+    assert_eq!(model.next_tick_ms(), Some(GHOST_CLEAR_MS));
+    model.handle(Event::Tick { elapsed_ms: 59_000 });
+    assert_eq!(model.next_tick_ms(), Some(1000));
+    assert_eq!(
+        model.handle(Event::Tick { elapsed_ms: 1000 }),
+        [Effect::FullRedraw, Effect::Sleep]
+    );
+    assert_eq!(model.screen, Screen::Clock);
+    assert_eq!(model.time, at("2024-05-06T07:08"));
+    assert!(!model.changed);
+    assert_eq!(model.next_tick_ms(), None);
+}
+
+#[test]
+fn timeout_can_save_first() {
+    let mut model = AdjustModel::new(
+        at("2024-05-06T07:08"),
+        false,
+        Settings {
+            save_on_timeout: true,
+            ..settings(PowerMode::PulsePowered)
+        },
+        None,
+        Vec::new(),
+    );
+    select(&mut model, AdjustField::Hours);
+    model.handle(Event::Press(Button::Backward));
+    model.handle(Event::Tick {
+        elapsed_ms: GHOST_CLEAR_MS,
+    });
+    let effects = model.handle(Event::Tick {
+        elapsed_ms: 60_000 - GHOST_CLEAR_MS,
+    });
+    assert_eq!(
+        effects,
+        [Effect::Save {
+            time: at("2024-05-06T06:08"),
+            timing: SaveTiming::NextPulse,
+        }]
+    );
+    assert_eq!(model.next_tick_ms(), None);
+    let effects = model.handle(Event::Stored {
+        time: at("2024-05-06T06:08"),
+        history: Vec::new(),
+    });
+    assert_eq!(effects, [Effect::FullRedraw, Effect::Sleep]);
+    assert_eq!(model.screen, Screen::Clock);
+    assert_eq!(model.clock, at("2024-05-06T06:08"));
+}
+
+#[test]
+fn timeout_while_counting_edges_keeps_running() {
+    let mut model = AdjustModel::new(
+        at("2024-05-06T07:08"),
+        true,
+        settings(PowerMode::Continuous),
+        None,
+        Vec::new(),
+    );
+    assert_eq!(model.next_tick_ms(), None);
+    model.handle(Event::Press(Button::Cycle));
+    assert_eq!(model.screen, Screen::Adjust);
+    assert_eq!(
+        model.handle(Event::Tick { elapsed_ms: 60_000 }),
+        [Effect::FullRedraw]
+    );
+    assert_eq!(model.screen, Screen::Clock);
+    assert!(model.handle(Event::Tick { elapsed_ms: 60_000 }).is_empty());
+}
@@ -0,0 +1,163 @@
+//! Telling bursts of catch-up pulses from the event log, over sequences of simulated boots.
+
+use paperslave_core::{
+    burst::{catching_up, held_back_boots, undrawn_pulse_boots},
+    event_log::{log_event, Event, SetSource},
+    flash::SimulatedFlash,
+    reset::ResetReason,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pulse {
+    /// Long enough to draw, even after waiting for a burst to settle.
+    Normal,
+    /// A catch-up pulse, over before anything is drawn.
+    Short,
+}
+
+fn boot_event(pulse_boot: bool, catching_up: bool) -> Event {
+    Event::Boot {
+        reset_reason: ResetReason::PowerOn,
+        counter: 0,
+        pulse_boot,
+        repaired: false,
+        corrected: false,
+        catching_up,
+    }
+}
+
+/// A pulse boot against `log`, holding back drawing after `min_rapid_boots` undrawn boots for at
+/// most `max_held_boots` boots in a row. Returns whether it was taken as catching up.
+fn boot(log: &mut SimulatedFlash, pulse: Pulse, min_rapid_boots: u32, max_held_boots: u32) -> bool {
+    let burst = catching_up(log, min_rapid_boots, max_held_boots).unwrap();
+    log_event(log, &boot_event(true, burst)).unwrap();
+    if pulse == Pulse::Normal {
+        let drawn = Event::Drawn {
+            total_us: 1_900_000,
+        };
+        log_event(log, &drawn).unwrap();
+    }
+    burst
+}
+
+#[test]
+fn counting_undrawn_boots() {
+    let drawn = Event::Drawn { total_us: 1000 };
+    assert_eq!(undrawn_pulse_boots(&[]), 0);
+    assert_eq!(undrawn_pulse_boots(&[boot_event(true, false), drawn]), 0);
+    assert_eq!(
+        undrawn_pulse_boots(&[
+            boot_event(true, false),
+            boot_event(true, false),
+            drawn,
+            boot_event(true, false),
+            boot_event(true, true),
+        ]),
+        2
+    );
+    assert_eq!(
+        held_back_boots(&[
+            boot_event(true, true),
+            boot_event(true, false),
+            boot_event(true, true),
+            boot_event(true, true),
+        ]),
+        2
+    );
+    /* A boot on USB power ends the run. */
+    assert_eq!(
+        undrawn_pulse_boots(&[boot_event(true, false), boot_event(false, false)]),
+        0
+    );
+}
+
+#[test]
+fn regular_pulses_are_never_held_back() {
+    let mut log = SimulatedFlash::new(2);
+    for _ in 0..500 {
+        assert!(!boot(&mut log, Pulse::Normal, 1, 50));
+    }
+}
+
+#[test]
+fn burst_is_held_back_until_it_settles() {
+    let mut log = SimulatedFlash::new(4);
+    assert!(!boot(&mut log, Pulse::Normal, 1, 50));
+    /* The first catch-up pulse looks like any other boot and starts drawing. */
+    assert!(!boot(&mut log, Pulse::Short, 1, 50));
+    for _ in 0..30 {
+        assert!(boot(&mut log, Pulse::Short, 1, 50));
+    }
+    /* The next regular pulse still waits, then draws. */
+    assert!(boot(&mut log, Pulse::Normal, 1, 50));
+    assert!(!boot(&mut log, Pulse::Normal, 1, 50));
+}
+
+#[test]
+fn threshold_of_several_undrawn_boots() {
+    let mut log = SimulatedFlash::new(4);
+    let burst: Vec<bool> = (0..6)
+        .map(|_| boot(&mut log, Pulse::Short, 3, 50))
+        .collect();
+    assert_eq!(burst, [false, false, false, true, true, true]);
+    assert!(boot(&mut log, Pulse::Normal, 3, 50));
+    assert!(!boot(&mut log, Pulse::Normal, 3, 50));
+
+    /* Two short pulses aren't enough. */
+    boot(&mut log, Pulse::Short, 3, 50);
+    boot(&mut log, Pulse::Short, 3, 50);
+    assert!(!boot(&mut log, Pulse::Normal, 3, 50));
+}
+
+#[test]
+fn held_back_boots_draw_anyway_after_the_limit() {
+    let mut log = SimulatedFlash::new(4);
+    let burst: Vec<bool> = (0..12)
+        .map(|_| boot(&mut log, Pulse::Short, 2, 4))
+        .collect();
+    assert_eq!(
+        burst,
+        [false, false, true, true, true, true, false, true, true, true, true, false]
+    );
+    assert!(boot(&mut log, Pulse::Normal, 2, 4));
+    assert!(!boot(&mut log, Pulse::Normal, 2, 4));
+}
+
+#[test]
+fn adjust_mode_ends_a_run_of_undrawn_boots() {
+    let mut log = SimulatedFlash::new(4);
+    boot(&mut log, Pulse::Short, 1, 50);
+    /* Someone plugged the board in and set the time. */
+    log_event(&mut log, &boot_event(false, false)).unwrap();
+    let set = Event::Set {
+        old: 0,
+        new: 100,
+        source: SetSource::Buttons,
+        pending_before: false,
+    };
+    log_event(&mut log, &set).unwrap();
+    assert!(!boot(&mut log, Pulse::Normal, 1, 50));
+
+    boot(&mut log, Pulse::Short, 1, 50);
+    log_event(&mut log, &Event::Pulse { counter: 101 }).unwrap();
+    assert!(!boot(&mut log, Pulse::Normal, 1, 50));
+}
+
+#[test]
+fn burst_across_a_sector_of_the_log() {
+    /* 204 records fit in a sector, so the burst starts at the end of the first one. */
+    let mut log = SimulatedFlash::new(2);
+    for _ in 0..101 {
+        boot(&mut log, Pulse::Normal, 3, 50);
+    }
+    let burst: Vec<bool> = (0..5)
+        .map(|_| boot(&mut log, Pulse::Short, 3, 50))
+        .collect();
+    assert_eq!(burst, [false, false, false, true, true]);
+}
+
+#[test]
+fn empty_log_is_not_a_burst() {
+    let log = SimulatedFlash::new(2);
+    assert!(!catching_up(&log, 1, 50).unwrap());
+}
@@ -0,0 +1,220 @@
+//! Button levels over simulated time through the button event layer of the firmware.
+
+use std::{cell::Cell, convert::Infallible, rc::Rc};
+
+use embedded_hal::digital::v2::InputPin;
+
+use paperslave_core::buttons::{ButtonEvent, ButtonTiming, Buttons};
+
+/// A pin whose level the test sets, shared with the test through `pressed`.
+struct MockPin {
+    pressed: Rc<Cell<bool>>,
+}
+
+impl InputPin for MockPin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(!self.pressed.get())
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(self.pressed.get())
+    }
+}
+
+const TIMING: ButtonTiming = ButtonTiming::DEFAULT;
+
+/// Two buttons, `A` and `B`, polled every 10 ms.
+struct Bench {
+    buttons: Buttons<char, MockPin>,
+    levels: [Rc<Cell<bool>>; 2],
+    now_ms: u64,
+}
+
+impl Bench {
+    fn new() -> Bench {
+        let levels = [Rc::new(Cell::new(false)), Rc::new(Cell::new(false))];
+        let pins = ['A', 'B']
+            .into_iter()
+            .zip(&levels)
+            .map(|(button, pressed)| {
+                let pressed = pressed.clone();
+                (button, MockPin { pressed })
+            })
+            .collect();
+        Bench {
+            buttons: Buttons::new(TIMING, pins),
+            levels,
+            now_ms: 0,
+        }
+    }
+
+    fn set(&mut self, button: char, pressed: bool) {
+        self.levels[(button as u8 - b'A') as usize].set(pressed);
+    }
+
+    /// Poll for `duration_ms` and return the events with their times.
+    fn run(&mut self, duration_ms: u64) -> Vec<(u64, ButtonEvent<char>)> {
+        let mut events = Vec::new();
+        for _ in 0..duration_ms / 10 {
+            self.now_ms += 10;
+            for event in self.buttons.poll(self.now_ms).unwrap() {
+                events.push((self.now_ms, event));
+            }
+        }
+        events
+    }
+
+    fn events(&mut self, duration_ms: u64) -> Vec<ButtonEvent<char>> {
+        self.run(duration_ms)
+            .into_iter()
+            .map(|(_, event)| event)
+            .collect()
+    }
+
+    /// Hold `button` for `duration_ms` and return the events until everything has settled.
+    fn press(&mut self, button: char, duration_ms: u64) -> Vec<ButtonEvent<char>> {
+        self.set(button, true);
+        let mut events = self.events(duration_ms);
+        self.set(button, false);
+        events.extend(self.events(1000));
+        events
+    }
+}
+
+#[test]
+fn short_press_is_a_click() {
+    let mut bench = Bench::new();
+    assert_eq!(
+        bench.press('A', 100),
+        [ButtonEvent::Down('A'), ButtonEvent::Click('A')]
+    );
+    assert!(!bench.buttons.busy());
+}
+
+#[test]
+fn bounces_are_ignored() {
+    let mut bench = Bench::new();
+    for _ in 0..5 {
+        bench.set('A', true);
+        assert!(bench.events(10).is_empty());
+        bench.set('A', false);
+        assert!(bench.events(10).is_empty());
+    }
+    bench.set('A', true);
+    assert_eq!(bench.events(100), [ButtonEvent::Down('A')]);
+    /* A dropout while held doesn't release it. */
+    bench.set('A', false);
+    assert!(bench.events(10).is_empty());
+    bench.set('A', true);
+    assert!(bench.events(100).is_empty());
+}
+
+#[test]
+fn two_quick_clicks_are_a_double_click() {
+    let mut bench = Bench::new();
+    bench.set('A', true);
+    bench.events(100);
+    bench.set('A', false);
+    bench.events(100);
+    assert_eq!(
+        bench.press('A', 100),
+        [ButtonEvent::Down('A'), ButtonEvent::DoubleClick('A')]
+    );
+}
+
+#[test]
+fn slow_clicks_stay_clicks() {
+    let mut bench = Bench::new();
+    let first = bench.press('A', 100);
+    let second = bench.press('A', 100);
+    assert_eq!(first, second);
+    assert_eq!(first, [ButtonEvent::Down('A'), ButtonEvent::Click('A')]);
+}
+
+#[test]
+fn holding_gives_a_long_press_and_accelerating_repeats() {
+    let mut bench = Bench::new();
+    bench.set('A', true);
+    let events = bench.run(3000);
+    bench.set('A', false);
+    let after_release = bench.events(1000);
+    assert!(after_release.is_empty(), "{after_release:?}");
+
+    assert_eq!(events[0].1, ButtonEvent::Down('A'));
+    let long_presses: Vec<u64> = events
+        .iter()
+        .filter(|(_, event)| *event == ButtonEvent::LongPress('A'))
+        .map(|&(time, _)| time)
+        .collect();
+    assert_eq!(long_presses.len(), 1);
+    assert!(long_presses[0] >= TIMING.long_press_ms);
+
+    let repeats: Vec<u64> = events
+        .iter()
+        .filter(|(_, event)| *event == ButtonEvent::Repeat('A'))
+        .map(|&(time, _)| time)
+        .collect();
+    assert!(repeats[0] >= TIMING.repeat_delay_ms);
+    let intervals: Vec<u64> = repeats.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    assert!(intervals.windows(2).all(|pair| pair[1] <= pair[0]));
+    assert!(intervals[0] > intervals[intervals.len() - 1]);
+    assert!(intervals
+        .iter()
+        .all(|&interval| interval >= TIMING.repeat_min_interval_ms));
+    /* Stepping the year by 20 shouldn't take long. */
+    assert!(repeats[19] < 2500, "{repeats:?}");
+}
+
+#[test]
+fn buttons_do_not_block_each_other() {
+    let mut bench = Bench::new();
+    bench.set('A', true);
+    bench.events(100);
+    bench.set('A', false);
+    bench.set('B', true);
+    let events = bench.events(100);
+    assert_eq!(events, [ButtonEvent::Down('B')]);
+    bench.set('B', false);
+    let events = bench.events(1000);
+    assert!(events.contains(&ButtonEvent::Click('A')));
+    assert!(events.contains(&ButtonEvent::Click('B')));
+}
+
+#[test]
+fn simultaneous_presses_are_a_chord() {
+    let mut bench = Bench::new();
+    bench.set('A', true);
+    bench.events(50);
+    bench.set('B', true);
+    let events = bench.events(2000);
+    assert_eq!(events, [ButtonEvent::Chord('A', 'B')]);
+    bench.set('A', false);
+    assert!(bench.events(100).is_empty());
+    bench.set('B', false);
+    assert!(bench.events(1000).is_empty());
+
+    /* Afterwards both work on their own again. */
+    assert_eq!(
+        bench.press('B', 100),
+        [ButtonEvent::Down('B'), ButtonEvent::Click('B')]
+    );
+}
+
+#[test]
+fn deadline_is_when_the_next_event_is_due() {
+    let mut bench = Bench::new();
+    assert_eq!(bench.buttons.next_deadline_ms(), None);
+    bench.set('A', true);
+    bench.events(10);
+    assert_eq!(
+        bench.buttons.next_deadline_ms(),
+        Some(10 + TIMING.debounce_ms)
+    );
+    bench.events(TIMING.debounce_ms);
+    assert_eq!(
+        bench.buttons.next_deadline_ms(),
+        Some(10 + TIMING.repeat_delay_ms)
+    );
+}
@@ -0,0 +1,10 @@
+//! Helpers shared by the integration tests.
+
+use chrono::NaiveDateTime;
+
+use paperslave_core::console::DATETIME_FORMAT;
+
+/// The time `datetime`, written like `2026-10-17T12:34`.
+pub fn at(datetime: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(datetime, DATETIME_FORMAT).unwrap()
+}
@@ -0,0 +1,248 @@
+//! Serial console commands against in-memory partitions.
+
+mod common;
+
+use common::at;
+use paperslave_core::{
+    audit::{undo_target, Save},
+    console::{execute, parse, Command, Effect, Outcome, ParseError, Partitions},
+    counter::{read_and_increment_counter, read_counter},
+    datetime::{counter_from_datetime, SaveTiming},
+    event_log::{log_event, Event, SetSource},
+    flash::SimulatedFlash,
+    profile::{record_timing, BootTiming},
+};
+
+fn partitions() -> Partitions<SimulatedFlash> {
+    Partitions {
+        counter: SimulatedFlash::new(4),
+        profile: SimulatedFlash::new(2),
+        log: SimulatedFlash::new(4),
+        saves: SimulatedFlash::new(2),
+    }
+}
+
+/// Parse and run `line`, showing saved times right away.
+fn run(partitions: &mut Partitions<SimulatedFlash>, line: &str) -> Outcome {
+    execute(partitions, parse(line).unwrap(), SaveTiming::Now).unwrap()
+}
+
+fn stored(datetime: &str) -> Option<Effect> {
+    Some(Effect::Stored(at(datetime)))
+}
+
+#[test]
+fn parse_commands() {
+    assert_eq!(parse("get"), Ok(Command::Get));
+    assert_eq!(
+        parse("  set   2026-10-17T12:34 "),
+        Ok(Command::Set(at("2026-10-17T12:34")))
+    );
+    assert_eq!(parse("add -3"), Ok(Command::Add(-3)));
+    assert_eq!(parse("add +90"), Ok(Command::Add(90)));
+    for (line, command) in [
+        ("status", Command::Status),
+        ("redraw", Command::Redraw),
+        ("timing", Command::Timing),
+        ("log", Command::Log),
+        ("history", Command::History),
+        ("undo", Command::Undo),
+    ] {
+        assert_eq!(parse(line), Ok(command));
+    }
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(parse(""), Err(ParseError::Empty));
+    assert_eq!(parse("   "), Err(ParseError::Empty));
+    assert_eq!(
+        parse("reboot"),
+        Err(ParseError::UnknownCommand("reboot".to_string()))
+    );
+    assert_eq!(parse("set"), Err(ParseError::MissingArgument));
+    assert_eq!(parse("add"), Err(ParseError::MissingArgument));
+    assert_eq!(
+        parse("set 2026-10-17"),
+        Err(ParseError::BadArgument("2026-10-17".to_string()))
+    );
+    assert_eq!(
+        parse("add five"),
+        Err(ParseError::BadArgument("five".to_string()))
+    );
+    assert_eq!(parse("get now"), Err(ParseError::TooManyArguments));
+    assert_eq!(parse("add 1 2"), Err(ParseError::TooManyArguments));
+}
+
+#[test]
+fn parse_refuses_moves_beyond_the_counter() {
+    assert_eq!(parse("add 4294967295"), Ok(Command::Add(u32::MAX as i64)));
+    assert_eq!(
+        parse("add -4294967295"),
+        Ok(Command::Add(-(u32::MAX as i64)))
+    );
+    for argument in ["4294967296", "9223372036854775807", "-9223372036854775808"] {
+        assert_eq!(
+            parse(&format!("add {argument}")),
+            Err(ParseError::BadArgument(argument.to_string()))
+        );
+    }
+}
+
+#[test]
+fn set_get_and_add() {
+    let mut partitions = partitions();
+    let outcome = run(&mut partitions, "set 2026-10-17T12:34");
+    assert_eq!(outcome.reply, "stored 2026-10-17T12:34");
+    assert_eq!(outcome.effect, stored("2026-10-17T12:34"));
+    assert_eq!(run(&mut partitions, "get").reply, "2026-10-17T12:34");
+
+    let outcome = run(&mut partitions, "add -35");
+    assert_eq!(outcome.effect, stored("2026-10-17T11:59"));
+    let outcome = run(&mut partitions, "add 1441");
+    assert_eq!(outcome.effect, stored("2026-10-18T12:00"));
+    assert_eq!(
+        read_counter(&mut partitions.counter).unwrap(),
+        counter_from_datetime(at("2026-10-18T12:00"))
+    );
+}
+
+#[test]
+fn add_saturates_at_the_ends_of_the_counter() {
+    let mut partitions = partitions();
+    run(&mut partitions, "set 2026-10-17T12:34");
+    let outcome = run(&mut partitions, "add -4294967295");
+    assert_eq!(outcome.effect, stored("1970-01-01T00:00"));
+    let outcome = run(&mut partitions, "add 4294967295");
+    assert_eq!(read_counter(&mut partitions.counter).unwrap(), u32::MAX);
+    assert!(
+        outcome.reply.starts_with("stored +10136-"),
+        "{}",
+        outcome.reply
+    );
+
+    /* Built directly instead of parsed, too large a move leaves the time as it is. */
+    let outcome = execute(&mut partitions, Command::Add(i64::MAX), SaveTiming::Now).unwrap();
+    assert_eq!(read_counter(&mut partitions.counter).unwrap(), u32::MAX);
+    assert!(
+        outcome.reply.starts_with("stored +10136-"),
+        "{}",
+        outcome.reply
+    );
+}
+
+#[test]
+fn set_for_the_next_pulse_is_shown_at_that_pulse() {
+    let mut partitions = partitions();
+    let command = parse("set 2026-10-17T12:34").unwrap();
+    let outcome = execute(&mut partitions, command, SaveTiming::NextPulse).unwrap();
+    assert_eq!(outcome.effect, stored("2026-10-17T12:34"));
+    assert!(run(&mut partitions, "status").reply.contains("offset 0"));
+}
+
+#[test]
+fn status_timing_and_redraw() {
+    let mut partitions = partitions();
+    assert_eq!(run(&mut partitions, "status").reply, "counter 0");
+    run(&mut partitions, "set 2026-10-17T12:34");
+    let value = counter_from_datetime(at("2026-10-17T12:34"));
+    assert_eq!(
+        run(&mut partitions, "status").reply,
+        format!(
+            "counter {value}, base {value}, offset 0, sector 0, erase count 1, \
+             corrected missed pulses 0"
+        )
+    );
+
+    assert_eq!(run(&mut partitions, "timing").reply, "no boots recorded");
+    let timing = BootTiming {
+        total_us: 1_480_000,
+        ..BootTiming::default()
+    };
+    record_timing(&mut partitions.profile, &timing).unwrap();
+    let reply = run(&mut partitions, "timing").reply;
+    assert_eq!(reply.lines().count(), 2);
+    assert!(reply.ends_with("worst of 1: counter 0 ms, render 0 ms, prepare 0 ms, quick clear 0 ms, draw 0 ms, total 1480 ms"), "{reply}");
+
+    let outcome = run(&mut partitions, "redraw");
+    assert_eq!(outcome.reply, "ok");
+    assert_eq!(outcome.effect, Some(Effect::Redraw));
+}
+
+#[test]
+fn log_history_and_undo() {
+    let mut partitions = partitions();
+    assert_eq!(run(&mut partitions, "log").reply, "log is empty");
+    assert_eq!(run(&mut partitions, "history").reply, "nothing saved");
+    let outcome = run(&mut partitions, "undo");
+    assert_eq!(outcome.reply, "nothing to undo");
+    assert_eq!(outcome.effect, None);
+
+    run(&mut partitions, "set 2026-10-17T12:34");
+    run(&mut partitions, "add 10");
+    let log = run(&mut partitions, "log").reply;
+    assert_eq!(log.lines().count(), 2);
+    assert!(
+        log.lines().all(|line| line.ends_with("on the console")),
+        "{log}"
+    );
+    assert_eq!(
+        run(&mut partitions, "history").reply,
+        "1970-01-01T00:00 -> 2026-10-17T12:34 (Console), 0 pulses since\n\
+         2026-10-17T12:34 -> 2026-10-17T12:44 (Console), 0 pulses since"
+    );
+
+    let outcome = run(&mut partitions, "undo");
+    assert_eq!(outcome.reply, "restored 2026-10-17T12:34");
+    assert_eq!(outcome.effect, stored("2026-10-17T12:34"));
+    assert_eq!(run(&mut partitions, "get").reply, "2026-10-17T12:34");
+}
+
+/// A pulse counted while running, the way adjust mode logs it.
+fn pulse(partitions: &mut Partitions<SimulatedFlash>) {
+    let counter = read_and_increment_counter(&mut partitions.counter).unwrap();
+    log_event(&mut partitions.log, &Event::Pulse { counter }).unwrap();
+}
+
+#[test]
+fn undo_after_pulses_keeps_them() {
+    let mut partitions = partitions();
+    let next_pulse = |partitions: &mut Partitions<SimulatedFlash>, line| {
+        execute(partitions, parse(line).unwrap(), SaveTiming::NextPulse).unwrap()
+    };
+    /* Set for the next pulse, then changed again before that pulse came. */
+    next_pulse(&mut partitions, "set 2026-10-17T12:34");
+    next_pulse(&mut partitions, "add 10");
+    for _ in 0..3 {
+        pulse(&mut partitions);
+    }
+    assert_eq!(run(&mut partitions, "get").reply, "2026-10-17T12:46");
+    assert!(run(&mut partitions, "history")
+        .reply
+        .ends_with("2026-10-17T12:34 -> 2026-10-17T12:44 (Console), 3 pulses since"));
+
+    /* Without the second save, the first pulse would have shown 12:34 and the others moved on. */
+    let outcome = run(&mut partitions, "undo");
+    assert_eq!(outcome.reply, "restored 2026-10-17T12:36");
+    pulse(&mut partitions);
+    assert_eq!(run(&mut partitions, "get").reply, "2026-10-17T12:37");
+}
+
+#[test]
+fn undo_targets() {
+    let save = |pending_before, pulses_after| Save {
+        old: 1000,
+        new: 5000,
+        source: SetSource::Buttons,
+        pending_before,
+        pending_after: false,
+        pulses_after,
+    };
+    /* Nothing counted since: back to the old value, still waiting for its pulse if it was. */
+    assert_eq!(undo_target(&save(false, 0)), (1000, false));
+    assert_eq!(undo_target(&save(true, 0)), (1000, true));
+    /* The first pulse would have been absorbed by a pending value. */
+    assert_eq!(undo_target(&save(false, 3)), (1003, false));
+    assert_eq!(undo_target(&save(true, 3)), (1002, false));
+    assert_eq!(undo_target(&save(true, 1)), (1000, false));
+}
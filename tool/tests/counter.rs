@@ -0,0 +1,517 @@
+//! The counter encoding against the simulated NOR flash, including power cuts at every write and
+//! erase, the order of sets and pulses, corruption and the migration from the single-sector
+//! layout.
+
+use paperslave_core::{
+    counter::{
+        self, check_offset_region, find_active_sector, read_and_increment_counter, read_counter,
+        repair_counter, set_counter, set_counter_now, Corruption, OFFSET_CAPACITY,
+    },
+    flash::{FlashStorage, SimulatedFlash, SimulatedFlashError, SECTOR_SIZE},
+};
+
+/// Flash holding `value` in its first sector, as if it had just been set and pulsed once.
+fn holding(sectors: u32, value: u32) -> SimulatedFlash {
+    let mut flash = SimulatedFlash::new(sectors);
+    set_counter(&mut flash, value).unwrap();
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), value);
+    flash
+}
+
+fn active_index(flash: &SimulatedFlash) -> u32 {
+    find_active_sector(flash).unwrap().unwrap().index
+}
+
+/// The erase counts in the headers, where the simulated flash has counted erases.
+fn assert_erase_counts_match(flash: &SimulatedFlash) {
+    let recorded: Vec<u32> = counter::erase_counts(flash)
+        .unwrap()
+        .into_iter()
+        .map(|count| count.unwrap_or(0))
+        .collect();
+    assert_eq!(recorded, flash.erase_counts());
+}
+
+/// Run `operation` on copies of `flash`, cutting the power at each of its writes and erases in
+/// turn. After every cut, the counter has to read `old` or `new` and go on counting from there. Returns
+/// the number of writes and erases the operation takes.
+fn assert_power_safe(
+    flash: &SimulatedFlash,
+    old: u32,
+    new: u32,
+    operation: impl Fn(&mut SimulatedFlash) -> Result<u32, SimulatedFlashError>,
+) -> u32 {
+    for steps in 0.. {
+        let mut cut = flash.clone();
+        cut.cut_power_after(steps);
+        if let Ok(value) = operation(&mut cut) {
+            assert!(cut.powered());
+            assert_eq!(value, new);
+            assert_eq!(read_counter(&mut cut).unwrap(), new);
+            return steps;
+        }
+        assert!(!cut.powered(), "failed without a power cut");
+        cut.restore_power();
+        let value = read_counter(&mut cut).unwrap();
+        assert!(
+            value == old || value == new,
+            "power cut after {steps} steps left {value}, not {old} or {new}"
+        );
+        /* A set value still waiting for its first pulse absorbs the increment. */
+        let pending = find_active_sector(&cut)
+            .unwrap()
+            .is_some_and(|a| a.pending_set);
+        let expected = if pending { value } else { value + 1 };
+        assert_eq!(read_and_increment_counter(&mut cut).unwrap(), expected);
+    }
+    unreachable!()
+}
+
+#[test]
+fn blank_partition_reads_zero_and_counts_from_there() {
+    let mut flash = SimulatedFlash::new(4);
+    assert_eq!(read_counter(&mut flash).unwrap(), 0);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 1);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 2);
+    assert_eq!(read_counter(&mut flash).unwrap(), 2);
+    assert_eq!(active_index(&flash), 0);
+}
+
+/// Flash whose last sector holds the counter the way it was stored before the ring: a 4-byte
+/// base, then the unary offset.
+fn legacy(sectors: u32, base: u32, offset_region: &[u8]) -> SimulatedFlash {
+    let mut flash = SimulatedFlash::new(sectors);
+    let start = (sectors - 1) * SECTOR_SIZE;
+    flash.write(start, &base.to_be_bytes()).unwrap();
+    flash.write(start + 4, offset_region).unwrap();
+    flash
+}
+
+#[test]
+fn legacy_counter_is_carried_into_the_ring() {
+    /* 19 increments since the base was written. */
+    let mut flash = legacy(4, 5000, &[0x00, 0x00, 0x1F]);
+    assert_eq!(read_counter(&mut flash).unwrap(), 5019);
+    assert!(find_active_sector(&flash).unwrap().is_none());
+
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 5020);
+    let active = find_active_sector(&flash).unwrap().unwrap();
+    assert_eq!(
+        (active.index, active.header.base, active.offset),
+        (0, 5020, 0)
+    );
+    assert!(!active.pending_set);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 5021);
+    assert_eq!(flash.erase_counts(), [1, 0, 0, 0]);
+}
+
+#[test]
+fn legacy_counter_with_an_erased_base_reads_zero() {
+    /* A fresh partition, or a set that was cut short after the erase. */
+    let mut flash = legacy(4, u32::MAX, &[]);
+    assert_eq!(read_counter(&mut flash).unwrap(), 0);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 1);
+    assert_eq!(active_index(&flash), 0);
+}
+
+#[test]
+fn legacy_counter_saturates_instead_of_wrapping() {
+    let mut flash = legacy(4, u32::MAX - 10, &[0x00, 0x00]);
+    assert_eq!(read_counter(&mut flash).unwrap(), u32::MAX);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), u32::MAX);
+    assert_eq!(read_counter(&mut flash).unwrap(), u32::MAX);
+}
+
+#[test]
+fn counter_saturates_at_the_top_instead_of_wrapping() {
+    let mut flash = holding(4, u32::MAX - 1);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), u32::MAX);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), u32::MAX);
+    assert_eq!(read_counter(&mut flash).unwrap(), u32::MAX);
+    /* Through the rollover into the next sector too. */
+    for _ in 0..OFFSET_CAPACITY {
+        assert_eq!(read_and_increment_counter(&mut flash).unwrap(), u32::MAX);
+    }
+    assert_eq!(active_index(&flash), 1);
+    assert_eq!(read_counter(&mut flash).unwrap(), u32::MAX);
+}
+
+#[test]
+fn every_increment_through_the_offset_region_into_the_next_sector() {
+    let mut flash = holding(4, 1000);
+    for offset in 1..=OFFSET_CAPACITY {
+        assert_eq!(
+            read_and_increment_counter(&mut flash).unwrap(),
+            1000 + offset
+        );
+    }
+    /* The offset region is full, but the value is still read from the first sector. */
+    let active = find_active_sector(&flash).unwrap().unwrap();
+    assert_eq!((active.index, active.offset), (0, OFFSET_CAPACITY));
+    assert_eq!(read_counter(&mut flash).unwrap(), 1000 + OFFSET_CAPACITY);
+    assert_eq!(flash.erase_counts(), [1, 0, 0, 0]);
+
+    let next = 1000 + OFFSET_CAPACITY + 1;
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), next);
+    let active = find_active_sector(&flash).unwrap().unwrap();
+    assert_eq!(
+        (active.index, active.header.base, active.offset),
+        (1, next, 0)
+    );
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), next + 1);
+    assert_eq!(flash.erase_counts(), [1, 1, 0, 0]);
+    assert_erase_counts_match(&flash);
+}
+
+#[test]
+fn ring_wraps_back_to_sector_zero() {
+    let mut flash = holding(2, 0);
+    let mut expected = 0;
+    /* Fill both sectors and start over in the first one. */
+    for lap in 0..3 {
+        for _ in 0..=OFFSET_CAPACITY {
+            expected += 1;
+            assert_eq!(read_and_increment_counter(&mut flash).unwrap(), expected);
+        }
+        assert_eq!(active_index(&flash), (lap + 1) % 2);
+    }
+    assert_eq!(read_counter(&mut flash).unwrap(), expected);
+    assert_eq!(flash.erase_counts(), [2, 2]);
+    assert_erase_counts_match(&flash);
+}
+
+#[test]
+fn sets_go_around_the_ring_and_spread_the_erases() {
+    let mut flash = SimulatedFlash::new(3);
+    for value in 0..10 {
+        set_counter(&mut flash, value * 100).unwrap();
+        assert_eq!(active_index(&flash), value % 3);
+        assert_eq!(read_counter(&mut flash).unwrap(), value * 100);
+        assert_erase_counts_match(&flash);
+    }
+    assert_eq!(flash.erase_counts(), [4, 3, 3]);
+}
+
+#[test]
+fn erase_counts_survive_a_damaged_header() {
+    let mut flash = SimulatedFlash::new(3);
+    for value in 0..5 {
+        set_counter(&mut flash, value).unwrap();
+    }
+    /* Sector 2, which is up next, loses its header to a stray write. */
+    assert_eq!(active_index(&flash), 1);
+    flash.write(2 * SECTOR_SIZE, &[0; 4]).unwrap();
+    set_counter(&mut flash, 5).unwrap();
+    assert_eq!(active_index(&flash), 2);
+    assert_erase_counts_match(&flash);
+}
+
+#[test]
+fn offset_region_shapes() {
+    assert_eq!(check_offset_region(&[0xFF; 4]), (0, None));
+    assert_eq!(check_offset_region(&[0x00, 0x07, 0xFF, 0xFF]), (13, None));
+    assert_eq!(check_offset_region(&[0x00; 4]), (32, None));
+
+    /* A flipped bit far ahead of the head is reported, but doesn't move the value. */
+    assert_eq!(
+        check_offset_region(&[0x00, 0x07, 0xFF, 0xEF]),
+        (13, Some(Corruption::StrayBits { index: 3 }))
+    );
+    /* Every cleared bit of a damaged head byte counts. */
+    let head = |byte| Some(Corruption::NonMonotonicHead { index: 1, byte });
+    assert_eq!(
+        check_offset_region(&[0x00, 0x1D, 0xFF, 0xFF]),
+        (12, head(0x1D))
+    );
+    assert_eq!(
+        check_offset_region(&[0x00, 0xFE, 0xFF, 0xFF]),
+        (9, head(0xFE))
+    );
+
+    /* A bit set again before the head is read past, over the cleared run after it. */
+    let set = |index| Some(Corruption::SetBits { index });
+    assert_eq!(
+        check_offset_region(&[0x00, 0x10, 0x00, 0x00, 0x1F, 0xFF]),
+        (35, set(1))
+    );
+    assert_eq!(
+        check_offset_region(&[0x08, 0x00, 0x00, 0x00, 0x00, 0xFF]),
+        (40, set(0))
+    );
+    /* Right before the head, only a byte that can't be a head is read past. */
+    assert_eq!(check_offset_region(&[0x00, 0x10, 0x1F, 0xFF]), (19, set(1)));
+    assert_eq!(
+        check_offset_region(&[0x00, 0x01, 0x1F, 0xFF]),
+        (15, Some(Corruption::StrayBits { index: 2 }))
+    );
+    /* Erased bytes in between mean the later bits are the stray ones, however many there are. */
+    assert_eq!(
+        check_offset_region(&[0x00, 0x07, 0xFF, 0x00, 0x00, 0x00]),
+        (13, Some(Corruption::StrayBits { index: 3 }))
+    );
+}
+
+/// Flash holding 1005 in sector 0, with `damage` written over its offset region at `index`.
+fn damaged(index: u32, damage: u8) -> SimulatedFlash {
+    let mut flash = holding(4, 1000);
+    for _ in 0..5 {
+        read_and_increment_counter(&mut flash).unwrap();
+    }
+    flash.write(64 + index, &[damage]).unwrap();
+    flash
+}
+
+/// Repair `flash`, which should be damaged by `corruption`, and check that the value is rewritten
+/// cleanly into the next sector.
+fn assert_repaired(flash: &mut SimulatedFlash, corruption: Corruption, value: u32) {
+    let active = find_active_sector(flash).unwrap().unwrap();
+    assert_eq!(active.corruption, Some(corruption));
+    assert_eq!(read_counter(flash).unwrap(), value);
+
+    assert_eq!(repair_counter(flash).unwrap(), Some(corruption));
+    let active = find_active_sector(flash).unwrap().unwrap();
+    assert_eq!(
+        (active.index, active.header.base, active.offset),
+        (1, value, 0)
+    );
+    assert_eq!(active.corruption, None);
+    assert_eq!(read_counter(flash).unwrap(), value);
+    assert_eq!(repair_counter(flash).unwrap(), None);
+    assert_eq!(active_index(flash), 1);
+    assert_erase_counts_match(flash);
+}
+
+#[test]
+fn stray_bit_near_the_end_does_not_move_the_clock() {
+    let mut flash = damaged(3000, 0xFE);
+    assert_repaired(&mut flash, Corruption::StrayBits { index: 3000 }, 1005);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 1006);
+}
+
+/// `flash` with the byte at `index` of the active offset region replaced by `byte`, which unlike a
+/// write can set bits.
+fn overwritten(flash: &SimulatedFlash, index: u32, byte: u8) -> SimulatedFlash {
+    let mut image = flash.image().to_vec();
+    let start = active_index(flash) * SECTOR_SIZE + 64;
+    image[(start + index) as usize] = byte;
+    SimulatedFlash::from_image(image)
+}
+
+#[test]
+fn bit_set_before_the_head_does_not_move_the_clock_back() {
+    /* 1000 and 44 increments leave the first 5 bytes cleared and 0x0F in the head byte. */
+    let mut flash = holding(4, 1000);
+    for _ in 0..44 {
+        read_and_increment_counter(&mut flash).unwrap();
+    }
+    for (index, byte) in [(0, 0x40), (2, 0x01), (4, 0x80)] {
+        let mut flash = overwritten(&flash, index, byte);
+        let active = find_active_sector(&flash).unwrap().unwrap();
+        assert_eq!(active.corruption, Some(Corruption::SetBits { index }));
+        assert_eq!(read_counter(&mut flash).unwrap(), 1044, "byte {index}");
+
+        assert_eq!(
+            repair_counter(&mut flash).unwrap(),
+            Some(Corruption::SetBits { index })
+        );
+        let active = find_active_sector(&flash).unwrap().unwrap();
+        assert_eq!((active.index, active.corruption), (1, None));
+        assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 1045);
+    }
+}
+
+#[test]
+fn bit_cleared_after_the_head_does_not_move_the_clock_ahead() {
+    let mut flash = holding(4, 1000);
+    for _ in 0..44 {
+        read_and_increment_counter(&mut flash).unwrap();
+    }
+    for index in [6, 7, 100, SECTOR_SIZE - 65] {
+        let mut flash = overwritten(&flash, index, 0xFB);
+        let active = find_active_sector(&flash).unwrap().unwrap();
+        assert_eq!(active.corruption, Some(Corruption::StrayBits { index }));
+        assert_eq!(read_counter(&mut flash).unwrap(), 1044, "byte {index}");
+        repair_counter(&mut flash).unwrap();
+        assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 1045);
+    }
+}
+
+#[test]
+fn non_monotonic_head_is_read_ahead_and_repaired() {
+    /* 5 increments leave 0x07 in the head byte. A cleared low bit makes it 0x06. */
+    let mut flash = damaged(0, 0x06);
+    let corruption = Corruption::NonMonotonicHead {
+        index: 0,
+        byte: 0x06,
+    };
+    assert_repaired(&mut flash, corruption, 1006);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 1007);
+}
+
+#[test]
+fn increment_moves_a_corrupted_counter_to_a_clean_sector() {
+    let mut flash = damaged(2000, 0x00);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 1006);
+    let active = find_active_sector(&flash).unwrap().unwrap();
+    assert_eq!((active.index, active.corruption), (1, None));
+    assert_eq!(read_counter(&mut flash).unwrap(), 1006);
+}
+
+#[test]
+fn repair_keeps_a_pending_set() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 500).unwrap();
+    flash.write(64 + 100, &[0x7F]).unwrap();
+    assert_eq!(
+        repair_counter(&mut flash).unwrap(),
+        Some(Corruption::StrayBits { index: 100 })
+    );
+    assert!(find_active_sector(&flash).unwrap().unwrap().pending_set);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 500);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 501);
+}
+
+#[test]
+fn repair_of_a_clean_or_blank_partition_does_nothing() {
+    let mut flash = SimulatedFlash::new(4);
+    assert_eq!(repair_counter(&mut flash).unwrap(), None);
+    let mut flash = holding(4, 1000);
+    assert_eq!(repair_counter(&mut flash).unwrap(), None);
+    assert_eq!(flash.erase_counts(), [1, 0, 0, 0]);
+}
+
+#[test]
+fn simulated_flash_enforces_nor_rules() {
+    let mut flash = SimulatedFlash::new(2);
+    flash.write(10, &[0x0F]).unwrap();
+    flash.write(10, &[0x05]).unwrap();
+    assert_eq!(
+        flash.write(9, &[0x00, 0xF5]),
+        Err(SimulatedFlashError::SetBit { offset: 10 })
+    );
+    /* The refused write left everything as it was. */
+    let mut byte = [0];
+    flash.read(9, &mut byte).unwrap();
+    assert_eq!(byte, [0xFF]);
+
+    assert_eq!(
+        flash.erase(100, SECTOR_SIZE),
+        Err(SimulatedFlashError::UnalignedErase)
+    );
+    assert_eq!(
+        flash.erase(0, 100),
+        Err(SimulatedFlashError::UnalignedErase)
+    );
+    assert_eq!(
+        flash.erase(SECTOR_SIZE, 2 * SECTOR_SIZE),
+        Err(SimulatedFlashError::OutOfBounds)
+    );
+    assert_eq!(
+        flash.write(2 * SECTOR_SIZE - 1, &[0, 0]),
+        Err(SimulatedFlashError::OutOfBounds)
+    );
+    assert_eq!(flash.erase_counts(), [0, 0]);
+
+    flash.erase(0, SECTOR_SIZE).unwrap();
+    flash.write(10, &[0xF0]).unwrap();
+    assert_eq!(flash.erase_counts(), [1, 0]);
+}
+
+#[test]
+fn power_cut_during_increment() {
+    let flash = holding(4, 1000);
+    let steps = assert_power_safe(&flash, 1000, 1001, read_and_increment_counter);
+    assert_eq!(steps, 1);
+}
+
+#[test]
+fn power_cut_during_rollover_into_the_next_sector() {
+    let mut flash = holding(4, 0);
+    for _ in 0..OFFSET_CAPACITY {
+        read_and_increment_counter(&mut flash).unwrap();
+    }
+    let full = OFFSET_CAPACITY;
+    let steps = assert_power_safe(&flash, full, full + 1, read_and_increment_counter);
+    /* Erase and header. */
+    assert_eq!(steps, 2);
+}
+
+#[test]
+fn power_cut_during_rollover_at_the_end_of_the_ring() {
+    let mut flash = SimulatedFlash::new(2);
+    for value in [10, 20] {
+        set_counter(&mut flash, value).unwrap();
+    }
+    assert_eq!(active_index(&flash), 1);
+    let steps = assert_power_safe(&flash, 20, 30, |flash| set_counter(flash, 30).map(|()| 30));
+    assert_eq!(steps, 2);
+    assert_eq!(flash.erase_counts(), [1, 1]);
+}
+
+#[test]
+fn power_cut_during_set() {
+    let flash = holding(4, 1000);
+    let steps = assert_power_safe(&flash, 1000, 5000, |flash| {
+        set_counter(flash, 5000).map(|()| 5000)
+    });
+    assert_eq!(steps, 2);
+}
+
+#[test]
+fn power_cut_during_set_now() {
+    let flash = holding(4, 1000);
+    let steps = assert_power_safe(&flash, 1000, 5000, |flash| {
+        set_counter_now(flash, 5000).map(|()| 5000)
+    });
+    assert_eq!(steps, 2);
+}
+
+#[test]
+fn power_cut_during_first_increment_of_a_blank_partition() {
+    let flash = SimulatedFlash::new(4);
+    assert_power_safe(&flash, 0, 1, read_and_increment_counter);
+}
+
+#[test]
+fn set_then_pulse_shows_the_set_value() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 500).unwrap();
+    assert_eq!(read_counter(&mut flash).unwrap(), 500);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 500);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 501);
+    assert_eq!(read_counter(&mut flash).unwrap(), 501);
+}
+
+#[test]
+fn pulse_then_set_shows_the_set_value() {
+    let mut flash = holding(4, 100);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 101);
+    set_counter(&mut flash, 500).unwrap();
+    assert_eq!(read_counter(&mut flash).unwrap(), 500);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 500);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 501);
+}
+
+#[test]
+fn set_then_set_shows_the_last_value_once() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 500).unwrap();
+    set_counter(&mut flash, 700).unwrap();
+    assert_eq!(read_counter(&mut flash).unwrap(), 700);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 700);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 701);
+
+    /* A set after the first pulse is absorbed again. */
+    set_counter(&mut flash, 900).unwrap();
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 900);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 901);
+}
+
+#[test]
+fn set_now_moves_on_at_the_next_pulse() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 500).unwrap();
+    set_counter_now(&mut flash, 700).unwrap();
+    assert_eq!(read_counter(&mut flash).unwrap(), 700);
+    assert_eq!(read_and_increment_counter(&mut flash).unwrap(), 701);
+}
@@ -0,0 +1,216 @@
+//! The record ring and the event log on top of it, including power cuts at every write and erase
+//! of an append.
+
+use paperslave_core::{
+    event_log::{log_event, read_events, read_latest_events, Event, SetSource},
+    flash::SimulatedFlash,
+    reset::ResetReason,
+    ring,
+};
+
+/// Number of 16-byte event records that fit in a sector.
+const EVENTS_PER_SECTOR: u32 = 204;
+
+fn pulse(counter: u32) -> Event {
+    Event::Pulse { counter }
+}
+
+/// A log of `sectors` sectors holding `count` pulses, counting from 0.
+fn log_with(sectors: u32, count: u32) -> SimulatedFlash {
+    let mut log = SimulatedFlash::new(sectors);
+    for counter in 0..count {
+        log_event(&mut log, &pulse(counter)).unwrap();
+    }
+    log
+}
+
+/// Append `event` to copies of `log`, cutting the power at each of its writes and erases in turn.
+/// After every cut, the events logged before have to read back unchanged, except for the `lost`
+/// oldest ones whose sector is erased to make room, and the log has to go on taking events.
+/// Returns the number of writes and erases the append takes.
+fn assert_power_safe(log: &SimulatedFlash, event: Event, lost: usize) -> u32 {
+    let before = read_events(log).unwrap();
+    let kept = &before[lost..];
+    for steps in 0.. {
+        let mut cut = log.clone();
+        cut.cut_power_after(steps);
+        if log_event(&mut cut, &event).is_ok() {
+            let after = read_events(&cut).unwrap();
+            assert_eq!(after.split_last(), Some((&event, kept)));
+            return steps;
+        }
+        assert!(!cut.powered(), "failed without a power cut");
+        cut.restore_power();
+        let after = read_events(&cut).unwrap();
+        assert!(
+            after == before || after == kept,
+            "power cut after {steps} steps lost events"
+        );
+
+        let next = pulse(u32::MAX);
+        log_event(&mut cut, &next).unwrap();
+        let again = read_events(&cut).unwrap();
+        let (last, rest) = again.split_last().unwrap();
+        assert_eq!(last, &next);
+        assert!(rest == before || rest == kept, "after {steps} steps");
+    }
+    unreachable!()
+}
+
+#[test]
+fn events_round_trip() {
+    let mut events = vec![
+        Event::Drawn {
+            total_us: 1_480_000,
+        },
+        pulse(123_456),
+        pulse(u32::MAX),
+    ];
+    for reset_reason in ResetReason::ALL {
+        for flags in 0..16 {
+            events.push(Event::Boot {
+                reset_reason,
+                counter: 29_000_000 + flags,
+                pulse_boot: flags & 1 != 0,
+                repaired: flags & 2 != 0,
+                corrected: flags & 4 != 0,
+                catching_up: flags & 8 != 0,
+            });
+        }
+    }
+    for source in [SetSource::Buttons, SetSource::Console, SetSource::Undo] {
+        for pending_before in [false, true] {
+            events.push(Event::Set {
+                old: 100,
+                new: u32::MAX - 1,
+                source,
+                pending_before,
+            });
+        }
+    }
+    for event in &events {
+        assert_eq!(Event::decode(&event.encode()), Some(*event), "{event:?}");
+    }
+
+    /* Through the ring, in order. */
+    let mut log = SimulatedFlash::new(4);
+    for event in &events {
+        log_event(&mut log, event).unwrap();
+    }
+    assert_eq!(read_events(&log).unwrap(), events);
+}
+
+#[test]
+fn unknown_records_are_not_events() {
+    assert_eq!(Event::decode(&[0xFF; 16]), None);
+    assert_eq!(Event::decode(&[0; 16]), None);
+    assert_eq!(Event::decode(&pulse(1).encode()[..15]), None);
+}
+
+#[test]
+fn events_read_as_text() {
+    let boot = Event::Boot {
+        reset_reason: ResetReason::PowerOn,
+        counter: 42,
+        pulse_boot: true,
+        repaired: false,
+        corrected: true,
+        catching_up: true,
+    };
+    assert_eq!(
+        boot.to_string(),
+        "boot PowerOn, counter 42, pulse, corrected, catching up"
+    );
+    let set = Event::Set {
+        old: 1,
+        new: 2,
+        source: SetSource::Undo,
+        pending_before: false,
+    };
+    assert_eq!(set.to_string(), "set from 1 to 2 by undo");
+    let drawn = Event::Drawn {
+        total_us: 1_480_000,
+    };
+    assert_eq!(drawn.to_string(), "drawn after 1480 ms");
+}
+
+#[test]
+fn ring_fills_sectors_in_order_and_wraps() {
+    let mut log = log_with(3, EVENTS_PER_SECTOR * 3);
+    let events = read_events(&log).unwrap();
+    assert_eq!(events.len() as u32, EVENTS_PER_SECTOR * 3);
+    assert_eq!(log.erase_counts(), [1, 1, 1]);
+
+    /* The next event erases the oldest sector. */
+    log_event(&mut log, &pulse(10_000)).unwrap();
+    let events = read_events(&log).unwrap();
+    let expected: Vec<Event> = (EVENTS_PER_SECTOR..EVENTS_PER_SECTOR * 3)
+        .map(pulse)
+        .chain([pulse(10_000)])
+        .collect();
+    assert_eq!(events, expected);
+    assert_eq!(log.erase_counts(), [2, 1, 1]);
+
+    /* Around the ring a few more times. */
+    for counter in 0..EVENTS_PER_SECTOR * 7 {
+        log_event(&mut log, &pulse(counter)).unwrap();
+    }
+    let events = read_events(&log).unwrap();
+    assert_eq!(events.len() as u32, EVENTS_PER_SECTOR * 2 + 1);
+    assert_eq!(events.last(), Some(&pulse(EVENTS_PER_SECTOR * 7 - 1)));
+    assert_eq!(log.erase_counts(), [4, 4, 3]);
+}
+
+#[test]
+fn latest_events_span_sectors() {
+    let log = log_with(3, EVENTS_PER_SECTOR * 4 + 2);
+    let all = read_events(&log).unwrap();
+    for count in [0, 1, 2, 3, 250, 1000] {
+        let latest = read_latest_events(&log, count).unwrap();
+        assert_eq!(latest, all[all.len().saturating_sub(count)..], "{count}");
+    }
+    assert_eq!(read_latest_events(&SimulatedFlash::new(2), 5).unwrap(), []);
+}
+
+#[test]
+fn records_of_another_size_are_not_read() {
+    let log = log_with(2, 3);
+    assert_eq!(ring::read_records(&log, 16).unwrap().len(), 3);
+    assert!(ring::read_records(&log, 24).unwrap().is_empty());
+}
+
+#[test]
+fn power_cut_during_first_append() {
+    let log = SimulatedFlash::new(2);
+    /* Erase, header and record. */
+    assert_eq!(assert_power_safe(&log, pulse(1), 0), 3);
+}
+
+#[test]
+fn power_cut_during_append_to_a_sector() {
+    let log = log_with(2, 10);
+    assert_eq!(assert_power_safe(&log, pulse(10), 0), 1);
+}
+
+#[test]
+fn power_cut_during_append_into_a_blank_sector() {
+    let log = log_with(3, EVENTS_PER_SECTOR);
+    assert_eq!(assert_power_safe(&log, pulse(1000), 0), 3);
+}
+
+#[test]
+fn power_cut_during_append_that_wraps_the_ring() {
+    let log = log_with(2, EVENTS_PER_SECTOR * 2);
+    let lost = EVENTS_PER_SECTOR as usize;
+    assert_eq!(assert_power_safe(&log, pulse(1000), lost), 3);
+}
+
+#[test]
+fn power_cut_after_a_torn_append() {
+    /* A torn slot is skipped by the next append as well as by reading. */
+    let mut log = log_with(2, 10);
+    log.cut_power_after(0);
+    assert!(log_event(&mut log, &pulse(10)).is_err());
+    log.restore_power();
+    assert_power_safe(&log, pulse(11), 0);
+}
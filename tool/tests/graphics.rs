@@ -0,0 +1,118 @@
+//! Drawing on the framebuffer through `embedded-graphics`.
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::{Gray8, GrayColor},
+    prelude::*,
+    primitives::{Circle, Line, PrimitiveStyle, Rectangle},
+    text::Text,
+};
+use paperslave_core::fb::{Framebuffer, Paint, BLACK, HEIGHT, WHITE, WIDTH};
+
+fn count(framebuffer: &Framebuffer, level: u8) -> usize {
+    framebuffer
+        .pixels()
+        .iter()
+        .filter(|&&pixel| pixel == level)
+        .count()
+}
+
+#[test]
+fn fill_solid_is_clipped_at_the_edges() {
+    let mut framebuffer = Framebuffer::new();
+    let corner = Rectangle::new(Point::new(-10, -10), Size::new(30, 30));
+    framebuffer.fill_solid(&corner, Gray8::BLACK).unwrap();
+    let across = Rectangle::new(Point::new(WIDTH - 5, 100), Size::new(10, 2));
+    framebuffer.fill_solid(&across, Gray8::new(0x80)).unwrap();
+    let outside = Rectangle::new(Point::new(0, HEIGHT), Size::new(10, 10));
+    framebuffer.fill_solid(&outside, Gray8::BLACK).unwrap();
+
+    assert_eq!(count(&framebuffer, BLACK), 20 * 20);
+    assert_eq!(framebuffer.get(19, 19), BLACK);
+    assert_eq!(framebuffer.get(20, 19), WHITE);
+    assert_eq!(count(&framebuffer, 0x80), 5 * 2);
+    assert_eq!(framebuffer.get(WIDTH - 1, 101), 0x80);
+}
+
+#[test]
+fn fill_contiguous_skips_the_colors_of_clipped_pixels() {
+    let mut framebuffer = Framebuffer::new();
+    let area = Rectangle::new(Point::new(WIDTH - 2, 0), Size::new(4, 2));
+    let colors = (0..8).map(|i| Gray8::new(i * 10));
+    framebuffer.fill_contiguous(&area, colors).unwrap();
+    assert_eq!(framebuffer.get(WIDTH - 2, 0), 0);
+    assert_eq!(framebuffer.get(WIDTH - 1, 0), 10);
+    assert_eq!(framebuffer.get(WIDTH - 2, 1), 40);
+    assert_eq!(framebuffer.get(WIDTH - 1, 1), 50);
+    assert_eq!(count(&framebuffer, WHITE), (WIDTH * HEIGHT - 4) as usize);
+}
+
+#[test]
+fn fill_contiguous_matches_pixel_by_pixel_drawing() {
+    let area = Rectangle::new(Point::new(100, 200), Size::new(37, 11));
+    let colors = || (0..37 * 11).map(|i| Gray8::new((i * 7 % 256) as u8));
+
+    let mut filled = Framebuffer::new();
+    filled.fill_contiguous(&area, colors()).unwrap();
+    let mut drawn = Framebuffer::new();
+    drawn
+        .draw_iter(area.points().zip(colors()).map(|(p, c)| Pixel(p, c)))
+        .unwrap();
+    assert!(filled.pixels() == drawn.pixels());
+}
+
+#[test]
+fn primitives_and_mono_text_draw_in_place() {
+    let mut framebuffer = Framebuffer::new();
+    Circle::with_center(Point::new(480, 270), 101)
+        .into_styled(PrimitiveStyle::with_fill(Gray8::BLACK))
+        .draw(&mut framebuffer)
+        .unwrap();
+    let area = count(&framebuffer, BLACK) as f64;
+    let expected = std::f64::consts::PI * 50.5 * 50.5;
+    assert!((area - expected).abs() / expected < 0.02, "{area}");
+
+    Line::new(Point::new(-20, 10), Point::new(WIDTH + 20, 10))
+        .into_styled(PrimitiveStyle::with_stroke(Gray8::new(0x40), 1))
+        .draw(&mut framebuffer)
+        .unwrap();
+    assert_eq!(count(&framebuffer, 0x40), WIDTH as usize);
+
+    let style = MonoTextStyle::new(&FONT_6X10, Gray8::BLACK);
+    let before = count(&framebuffer, BLACK);
+    Text::new("12:34", Point::new(10, 500), style)
+        .draw(&mut framebuffer)
+        .unwrap();
+    assert!(count(&framebuffer, BLACK) > before);
+    assert_eq!(framebuffer.get(9, 500), WHITE);
+}
+
+#[test]
+fn painted_combines_with_what_is_drawn() {
+    let mut framebuffer = Framebuffer::new();
+    let square = Rectangle::new(Point::new(10, 10), Size::new(20, 20));
+    square
+        .into_styled(PrimitiveStyle::with_fill(Gray8::new(0xC0)))
+        .draw(&mut framebuffer.painted(Paint::Darken))
+        .unwrap();
+    assert_eq!(framebuffer.get(15, 15), 0xC0);
+    /* Darkening again adds up, and light gray doesn't lighten when darkening. */
+    square
+        .into_styled(PrimitiveStyle::with_fill(Gray8::new(0xC0)))
+        .draw(&mut framebuffer.painted(Paint::Darken))
+        .unwrap();
+    let twice = WHITE - 2 * (WHITE - 0xC0);
+    assert_eq!(framebuffer.get(15, 15), twice);
+
+    /* White lightens fully, black not at all, and drawing off screen is ignored. */
+    Rectangle::new(Point::new(-5, 12), Size::new(40, 2))
+        .into_styled(PrimitiveStyle::with_fill(Gray8::WHITE))
+        .draw(&mut framebuffer.painted(Paint::Lighten))
+        .unwrap();
+    Rectangle::new(Point::new(10, 20), Size::new(20, 2))
+        .into_styled(PrimitiveStyle::with_fill(Gray8::BLACK))
+        .draw(&mut framebuffer.painted(Paint::Lighten))
+        .unwrap();
+    assert_eq!(framebuffer.get(15, 12), WHITE);
+    assert_eq!(framebuffer.get(15, 20), twice);
+}
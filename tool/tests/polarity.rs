@@ -0,0 +1,170 @@
+//! Missed pulse detection from the line polarity, and how the counter stores the phase and the
+//! corrections.
+
+use paperslave_core::{
+    counter::{
+        count_pulse, find_active_sector, read_counter, record_correction, set_counter,
+        set_counter_now, set_polarity_phase, ActiveSector, OFFSET_CAPACITY,
+    },
+    flash::SimulatedFlash,
+    polarity::{check, phase_of, PolarityCheck},
+};
+
+fn active(flash: &SimulatedFlash) -> ActiveSector {
+    find_active_sector(flash).unwrap().unwrap()
+}
+
+/// A pulse boot with polarity `high`. Returns the value shown and whether a missed pulse was
+/// corrected.
+fn pulse(flash: &mut SimulatedFlash, high: bool) -> (u32, bool) {
+    count_pulse(flash, Some(high)).unwrap()
+}
+
+#[test]
+fn check_learns_then_compares() {
+    assert_eq!(check(None, 10, true), PolarityCheck::Learned(true));
+    assert_eq!(check(None, 11, true), PolarityCheck::Learned(false));
+    assert_eq!(check(None, 11, false), PolarityCheck::Learned(true));
+
+    /* The line alternates with every pulse, so consecutive values keep the phase. */
+    for value in 10..20 {
+        let high = value % 2 == 0;
+        assert!(phase_of(value, high));
+        assert_eq!(check(Some(true), value, high), PolarityCheck::Matched);
+        assert_eq!(check(Some(false), value, high), PolarityCheck::Missed);
+        assert_eq!(check(Some(true), value, !high), PolarityCheck::Missed);
+    }
+}
+
+#[test]
+fn missed_pulse_is_corrected() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 100).unwrap();
+    assert_eq!(pulse(&mut flash, true), (100, false));
+    assert_eq!(active(&flash).polarity_phase, Some(true));
+    assert_eq!(pulse(&mut flash, false), (101, false));
+    assert_eq!(pulse(&mut flash, true), (102, false));
+
+    /* The low pulse for 103 never arrived. */
+    assert_eq!(pulse(&mut flash, true), (104, true));
+    assert_eq!(active(&flash).corrections(), 1);
+    assert_eq!(pulse(&mut flash, false), (105, false));
+    assert_eq!(read_counter(&mut flash).unwrap(), 105);
+}
+
+#[test]
+fn without_a_polarity_pulses_are_only_counted() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 100).unwrap();
+    assert_eq!(count_pulse(&mut flash, None).unwrap(), (100, false));
+    assert_eq!(count_pulse(&mut flash, None).unwrap(), (101, false));
+    assert_eq!(active(&flash).polarity_phase, None);
+
+    /* A learned phase is left alone while the polarity can't be read. */
+    assert_eq!(pulse(&mut flash, true), (102, false));
+    assert_eq!(count_pulse(&mut flash, None).unwrap(), (103, false));
+    assert_eq!(count_pulse(&mut flash, None).unwrap(), (104, false));
+    assert_eq!(active(&flash).polarity_phase, Some(true));
+    assert_eq!(active(&flash).corrections(), 0);
+}
+
+#[test]
+fn missed_pulse_on_a_blank_partition_is_learned() {
+    let mut flash = SimulatedFlash::new(4);
+    assert_eq!(pulse(&mut flash, false), (1, false));
+    assert_eq!(active(&flash).polarity_phase, Some(true));
+    assert_eq!(pulse(&mut flash, false), (3, true));
+    assert_eq!(read_counter(&mut flash).unwrap(), 3);
+}
+
+#[test]
+fn phase_is_stored_once_per_sector() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 100).unwrap();
+    assert_eq!(active(&flash).polarity_phase, None);
+    set_polarity_phase(&mut flash, false).unwrap();
+    set_polarity_phase(&mut flash, true).unwrap();
+    assert_eq!(active(&flash).polarity_phase, Some(false));
+
+    /* A blank partition has nowhere to store it yet. */
+    let mut blank = SimulatedFlash::new(4);
+    set_polarity_phase(&mut blank, true).unwrap();
+    assert!(find_active_sector(&blank).unwrap().is_none());
+}
+
+#[test]
+fn phase_carries_over_to_the_next_sector() {
+    let mut flash = SimulatedFlash::new(2);
+    set_counter(&mut flash, 0).unwrap();
+    let mut high = true;
+    for _ in 0..=OFFSET_CAPACITY + 1 {
+        assert!(!pulse(&mut flash, high).1);
+        high = !high;
+    }
+    let active = active(&flash);
+    assert_eq!(active.index, 1);
+    assert_eq!(active.polarity_phase, Some(true));
+}
+
+#[test]
+fn set_forgets_the_phase() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 100).unwrap();
+    pulse(&mut flash, true);
+    set_counter(&mut flash, 500).unwrap();
+    assert_eq!(active(&flash).polarity_phase, None);
+    /* The next pulse may have either polarity and is learned rather than corrected. */
+    assert_eq!(pulse(&mut flash, true), (500, false));
+    assert_eq!(active(&flash).polarity_phase, Some(true));
+}
+
+#[test]
+fn set_now_flips_the_phase_with_the_parity_of_the_jump() {
+    for (value, phase) in [(102, Some(true)), (205, Some(false)), (100, Some(true))] {
+        let mut flash = SimulatedFlash::new(4);
+        set_counter(&mut flash, 100).unwrap();
+        pulse(&mut flash, true);
+        set_counter_now(&mut flash, value).unwrap();
+        assert_eq!(active(&flash).polarity_phase, phase, "set to {value}");
+        /* The line goes on alternating, so the next pulse matches. */
+        assert_eq!(pulse(&mut flash, false), (value + 1, false));
+    }
+
+    /* Nothing learned, nothing to carry over. */
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 100).unwrap();
+    set_counter_now(&mut flash, 103).unwrap();
+    assert_eq!(active(&flash).polarity_phase, None);
+}
+
+#[test]
+fn corrections_are_summed_across_sectors() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 100).unwrap();
+    for _ in 0..3 {
+        record_correction(&mut flash).unwrap();
+    }
+    assert_eq!(active(&flash).corrections(), 3);
+    set_counter_now(&mut flash, 200).unwrap();
+    record_correction(&mut flash).unwrap();
+    let active = active(&flash);
+    assert_eq!(active.header.corrections, 3);
+    assert_eq!(active.corrections(), 4);
+}
+
+#[test]
+fn corrections_saturate_in_a_sector() {
+    let mut flash = SimulatedFlash::new(4);
+    set_counter(&mut flash, 100).unwrap();
+    for _ in 0..300 {
+        record_correction(&mut flash).unwrap();
+    }
+    /* The 24 bytes between the markers and the offset region. */
+    assert_eq!(active(&flash).corrections(), 192);
+    assert_eq!(read_counter(&mut flash).unwrap(), 100);
+
+    /* Nothing to count on a blank partition. */
+    let mut blank = SimulatedFlash::new(4);
+    record_correction(&mut blank).unwrap();
+    assert!(find_active_sector(&blank).unwrap().is_none());
+}
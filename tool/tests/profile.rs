@@ -0,0 +1,105 @@
+//! Boot timings in the profile ring and their worst case.
+
+use paperslave_core::{
+    flash::{SimulatedFlash, SimulatedFlashError},
+    profile::{read_timings, record_timing, BootTiming},
+};
+
+/// A boot that took `total_us` in all, spent drawing.
+fn took(total_us: u32) -> BootTiming {
+    BootTiming {
+        draw_us: total_us,
+        total_us,
+        ..BootTiming::default()
+    }
+}
+
+#[test]
+fn timings_are_read_back_oldest_first() {
+    let mut flash = SimulatedFlash::new(4);
+    assert_eq!(read_timings(&flash).unwrap(), []);
+    let timing = BootTiming {
+        counter_us: 1,
+        render_us: 2,
+        prepare_us: 3,
+        quick_clear_us: 4,
+        draw_us: 5,
+        total_us: 6,
+    };
+    record_timing(&mut flash, &timing).unwrap();
+    record_timing(&mut flash, &took(1000)).unwrap();
+    assert_eq!(read_timings(&flash).unwrap(), [timing, took(1000)]);
+    assert_eq!(BootTiming::decode(&timing.encode()), Some(timing));
+    assert_eq!(BootTiming::decode(&[0; 23]), None);
+}
+
+#[test]
+fn ring_wraps_and_keeps_the_latest_boots() {
+    /* 145 records fit in a sector. */
+    let mut flash = SimulatedFlash::new(2);
+    for total_us in 0..145 * 3 {
+        record_timing(&mut flash, &took(total_us)).unwrap();
+    }
+    let timings = read_timings(&flash).unwrap();
+    let expected: Vec<BootTiming> = (145..145 * 3).map(took).collect();
+    assert_eq!(timings, expected);
+    assert_eq!(flash.erase_counts(), [2, 1]);
+
+    /* One more starts over in the sector with the oldest boots. */
+    record_timing(&mut flash, &took(5000)).unwrap();
+    let timings = read_timings(&flash).unwrap();
+    assert_eq!(timings.len(), 146);
+    assert_eq!(timings.first(), Some(&took(290)));
+    assert_eq!(timings.last(), Some(&took(5000)));
+    assert_eq!(flash.erase_counts(), [2, 2]);
+}
+
+#[test]
+fn worst_case_is_taken_per_phase() {
+    assert_eq!(BootTiming::worst_case(&[]), None);
+
+    let mut flash = SimulatedFlash::new(4);
+    for boot in 0..300_u32 {
+        /* Each phase has its slowest boot at a different point. */
+        let timing = BootTiming {
+            counter_us: if boot == 10 { 90_000 } else { boot },
+            render_us: if boot == 150 { 400_000 } else { 1000 },
+            prepare_us: 300 - boot,
+            quick_clear_us: 200_000,
+            draw_us: if boot == 299 { 700_000 } else { 500_000 },
+            total_us: 1_200_000 + boot % 7 * 1000,
+        };
+        record_timing(&mut flash, &timing).unwrap();
+    }
+    let worst = BootTiming::worst_case(&read_timings(&flash).unwrap()).unwrap();
+    assert_eq!(
+        worst,
+        BootTiming {
+            counter_us: 90_000,
+            render_us: 400_000,
+            prepare_us: 300,
+            quick_clear_us: 200_000,
+            draw_us: 700_000,
+            total_us: 1_206_000,
+        }
+    );
+}
+
+#[test]
+fn torn_record_is_skipped() {
+    let mut flash = SimulatedFlash::new(4);
+    record_timing(&mut flash, &took(1000)).unwrap();
+    flash.cut_power_after(0);
+    assert_eq!(
+        record_timing(&mut flash, &took(9_000_000)),
+        Err(SimulatedFlashError::PowerLoss)
+    );
+    flash.restore_power();
+    assert_eq!(read_timings(&flash).unwrap(), [took(1000)]);
+
+    /* The next boot goes on after the torn slot. */
+    record_timing(&mut flash, &took(2000)).unwrap();
+    let timings = read_timings(&flash).unwrap();
+    assert_eq!(timings, [took(1000), took(2000)]);
+    assert_eq!(BootTiming::worst_case(&timings), Some(took(2000)));
+}
@@ -0,0 +1,111 @@
+//! Pulse recognition from the edges of a bouncy input.
+
+use paperslave_core::pulse::{counts_edges, PowerMode, PulseDetector};
+
+const MS: i64 = 1000;
+const MIN_LENGTH_US: i64 = 50 * MS;
+const HOLDOFF_US: i64 = 30_000 * MS;
+
+/// Feed `edges` as (milliseconds, active) to a new detector and return the times of the edges that
+/// completed a pulse.
+fn pulses(edges: &[(i64, bool)]) -> Vec<i64> {
+    let mut detector = PulseDetector::new(MIN_LENGTH_US, HOLDOFF_US);
+    edges
+        .iter()
+        .filter(|&&(time_ms, active)| detector.edge(time_ms * MS, active))
+        .map(|&(time_ms, _)| time_ms)
+        .collect()
+}
+
+#[test]
+fn clean_pulses_are_counted_at_their_end() {
+    assert_eq!(
+        pulses(&[(0, true), (500, false), (60_000, true), (60_500, false)]),
+        [500, 60_500]
+    );
+}
+
+#[test]
+fn short_glitches_are_ignored() {
+    assert_eq!(pulses(&[(0, true), (49, false)]), [] as [i64; 0]);
+    assert_eq!(pulses(&[(0, true), (50, false)]), [50]);
+    /* A glitch before a pulse does not shorten it or start the holdoff. */
+    assert_eq!(
+        pulses(&[(0, true), (1, false), (5, true), (505, false)]),
+        [505]
+    );
+}
+
+#[test]
+fn bounces_within_a_pulse_count_once() {
+    let edges = [
+        (0, true),
+        (2, false),
+        (3, true),
+        (200, false),
+        (201, true),
+        (400, false),
+        (402, true),
+        (403, false),
+    ];
+    assert_eq!(pulses(&edges), [200]);
+}
+
+#[test]
+fn holdoff_ends_after_the_pulse_that_was_counted() {
+    let edges = [
+        (0, true),
+        (500, false),
+        (30_400, true),
+        (30_499, false),
+        (30_501, true),
+        (30_600, false),
+    ];
+    assert_eq!(pulses(&edges), [500, 30_600]);
+}
+
+#[test]
+fn repeated_levels_are_ignored() {
+    let edges = [(0, true), (100, true), (300, false), (301, false)];
+    assert_eq!(pulses(&edges), [300]);
+    /* Going active again does not restart the pulse. */
+    assert_eq!(pulses(&[(0, true), (40, true), (60, false)]), [60]);
+}
+
+#[test]
+fn pulse_active_at_start_is_not_counted() {
+    /* The pulse that powered up the board ends, bouncing, and the next one is counted. */
+    let edges = [
+        (300, false),
+        (301, true),
+        (400, false),
+        (402, true),
+        (600, false),
+        (60_000, true),
+        (60_500, false),
+    ];
+    assert_eq!(pulses(&edges), [60_500]);
+}
+
+#[test]
+fn missing_active_edge_later_is_not_a_pulse() {
+    /* Only the first edge can end a pulse from before the detector, so a lost active edge later
+     * loses a pulse without holding off the next one. */
+    let edges = [
+        (0, true),
+        (500, false),
+        (60_500, false),
+        (61_000, true),
+        (61_500, false),
+    ];
+    assert_eq!(pulses(&edges), [500, 61_500]);
+}
+
+#[test]
+fn edges_are_counted_by_power_mode() {
+    for seen in [false, true] {
+        assert!(!counts_edges(PowerMode::PulsePowered, seen));
+        assert!(counts_edges(PowerMode::Continuous, seen));
+        assert_eq!(counts_edges(PowerMode::Auto, seen), seen);
+    }
+}
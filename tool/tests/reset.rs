@@ -0,0 +1,54 @@
+//! Which boots count as a minute pulse.
+
+use paperslave_core::reset::{is_pulse_boot, ResetReason};
+
+#[test]
+fn pulse_power_on_counts() {
+    assert!(is_pulse_boot(ResetReason::PowerOn, None));
+    assert!(is_pulse_boot(ResetReason::PowerOn, Some(false)));
+}
+
+#[test]
+fn usb_power_on_does_not_count() {
+    assert!(!is_pulse_boot(ResetReason::PowerOn, Some(true)));
+}
+
+#[test]
+fn resets_while_powered_do_not_count() {
+    for reason in [
+        ResetReason::External,
+        ResetReason::Software,
+        ResetReason::Panic,
+        ResetReason::InterruptWatchdog,
+        ResetReason::TaskWatchdog,
+        ResetReason::OtherWatchdog,
+        ResetReason::DeepSleep,
+        ResetReason::Brownout,
+        ResetReason::Sdio,
+        ResetReason::Unknown,
+    ] {
+        for usb_powered in [None, Some(false), Some(true)] {
+            assert!(
+                !is_pulse_boot(reason, usb_powered),
+                "{reason:?} with USB power {usb_powered:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn en_pin_reset_counts_without_vbus_sense() {
+    /* espflash and the reset button pull EN low, which the ESP32 reports as a power-on. Only the
+     * VBUS sense pin tells it apart from a pulse. */
+    let en_pin_reset = ResetReason::PowerOn;
+    assert!(is_pulse_boot(en_pin_reset, None));
+    assert!(!is_pulse_boot(en_pin_reset, Some(true)));
+}
+
+#[test]
+fn codes_round_trip() {
+    for reason in ResetReason::ALL {
+        assert_eq!(ResetReason::from_code(reason.code()), reason);
+    }
+    assert_eq!(ResetReason::from_code(200), ResetReason::Unknown);
+}
@@ -0,0 +1,228 @@
+//! Antialiased lines, circles, arcs and polygons, checked against the areas they should cover.
+//! What they look like is compared with the golden images in `snapshots.rs`.
+
+use std::f32::consts::{PI, TAU};
+
+use paperslave_core::{
+    fb::{Framebuffer, Paint, Rect, BLACK, HEIGHT, WHITE, WIDTH},
+    shapes::{FillRule, Point, Style},
+};
+
+/// Area covered, in pixels, counting gray pixels by how dark they are.
+fn ink(framebuffer: &Framebuffer) -> f32 {
+    framebuffer
+        .pixels()
+        .iter()
+        .map(|&pixel| (WHITE - pixel) as f32 / WHITE as f32)
+        .sum()
+}
+
+fn assert_area(framebuffer: &Framebuffer, expected: f32) {
+    let area = ink(framebuffer);
+    assert!(
+        (area - expected).abs() / expected < 0.01,
+        "covered {area}, expected {expected}"
+    );
+}
+
+fn drawn(draw: impl FnOnce(&mut Framebuffer)) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new();
+    draw(&mut framebuffer);
+    framebuffer
+}
+
+fn star(center: Point, radius: f32) -> Vec<Point> {
+    (0..5)
+        .map(|i| {
+            let angle = -TAU / 4. + i as f32 * 2. * TAU / 5.;
+            Point::new(
+                center.x + radius * angle.cos(),
+                center.y + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn thick_lines_cover_their_area() {
+    let (from, to) = (Point::new(100.3, 80.), Point::new(700., 420.6));
+    let length = (to.x - from.x).hypot(to.y - from.y);
+    for width in [1., 3.5, 12.] {
+        let framebuffer = drawn(|fb| fb.line(Paint::Darken, from, to, width));
+        /* Round ends add a circle of the width. */
+        assert_area(&framebuffer, length * width + PI * width * width / 4.);
+    }
+}
+
+#[test]
+fn thin_lines_are_lighter() {
+    let (from, to) = (Point::new(10., 100.5), Point::new(500., 100.5));
+    let framebuffer = drawn(|fb| fb.line(Paint::Darken, from, to, 0.5));
+    assert_eq!(framebuffer.get(200, 100), WHITE - WHITE / 2 - 1);
+    assert_eq!(framebuffer.get(200, 99), WHITE);
+    assert_eq!(framebuffer.get(200, 101), WHITE);
+}
+
+#[test]
+fn circles_cover_their_area() {
+    let center = Point::new(480.2, 270.7);
+    let filled = drawn(|fb| fb.circle(Paint::Darken, center, 100., Style::Fill));
+    assert_area(&filled, PI * 100. * 100.);
+    assert_eq!(filled.get(480, 270), BLACK);
+    assert_eq!(filled.get(480, 160), WHITE);
+
+    let stroked = drawn(|fb| fb.circle(Paint::Darken, center, 100., Style::Stroke { width: 8. }));
+    assert_area(&stroked, TAU * 100. * 8.);
+    assert_eq!(stroked.get(480, 270), WHITE);
+    assert_eq!(stroked.get(580, 270), BLACK);
+}
+
+#[test]
+fn arcs_cover_their_area() {
+    let center = Point::new(480., 270.);
+    for sweep in [TAU / 4., TAU / 2., 3. * TAU / 4.] {
+        let slice = drawn(|fb| fb.arc(Paint::Darken, center, 150., 0.3, sweep, Style::Fill));
+        assert_area(&slice, 150. * 150. * sweep / 2.);
+
+        let stroke = Style::Stroke { width: 6. };
+        let arc = drawn(|fb| fb.arc(Paint::Darken, center, 150., 0.3, sweep, stroke));
+        assert_area(&arc, 150. * sweep * 6. + PI * 9.);
+    }
+}
+
+#[test]
+fn arcs_go_clockwise_from_the_right() {
+    let center = Point::new(480., 270.);
+    /* The top right quarter, starting from straight up. */
+    let quarter = drawn(|fb| {
+        fb.arc(
+            Paint::Darken,
+            center,
+            100.,
+            -TAU / 4.,
+            TAU / 4.,
+            Style::Fill,
+        )
+    });
+    assert_eq!(quarter.get(530, 220), BLACK);
+    assert_eq!(quarter.get(430, 220), WHITE);
+    assert_eq!(quarter.get(530, 320), WHITE);
+
+    /* A negative sweep goes back, counterclockwise. */
+    let back = drawn(|fb| fb.arc(Paint::Darken, center, 100., 0., -TAU / 4., Style::Fill));
+    assert!(back.pixels() == quarter.pixels());
+}
+
+#[test]
+fn polygon_with_pixel_edges_has_no_gray() {
+    let square = [
+        Point::new(10., 20.),
+        Point::new(40., 20.),
+        Point::new(40., 30.),
+        Point::new(10., 30.),
+    ];
+    let framebuffer = drawn(|fb| fb.polygon(Paint::Darken, &square, FillRule::NonZero));
+    let expected = drawn(|fb| {
+        let rect = Rect {
+            x: 10,
+            y: 20,
+            w: 30,
+            h: 10,
+        };
+        fb.rect(Paint::Darken, rect)
+    });
+    assert!(framebuffer.pixels() == expected.pixels());
+}
+
+#[test]
+fn polygon_fill_rules() {
+    let center = Point::new(480., 270.);
+    let points = star(center, 200.);
+    let even_odd = drawn(|fb| fb.polygon(Paint::Darken, &points, FillRule::EvenOdd));
+    let non_zero = drawn(|fb| fb.polygon(Paint::Darken, &points, FillRule::NonZero));
+
+    /* The pentagon in the middle is wound around twice. */
+    assert_eq!(even_odd.get(480, 270), WHITE);
+    assert_eq!(non_zero.get(480, 270), BLACK);
+    /* The points are inside either way. */
+    assert_eq!(even_odd.get(480, 90), BLACK);
+    assert_eq!(non_zero.get(480, 90), BLACK);
+
+    let inner = 200. * (TAU / 5.).cos() / (TAU / 10.).cos();
+    let pentagon = 5. / 2. * inner * inner * (TAU / 5.).sin();
+    assert_area(&non_zero, ink(&even_odd) + pentagon);
+
+    /* Going around the other way doesn't change what is inside. */
+    let reversed: Vec<Point> = points.iter().rev().copied().collect();
+    let backwards = drawn(|fb| fb.polygon(Paint::Darken, &reversed, FillRule::NonZero));
+    assert!(backwards.pixels() == non_zero.pixels());
+}
+
+#[test]
+fn shapes_are_clipped_at_the_edges() {
+    let corner = drawn(|fb| fb.circle(Paint::Darken, Point::new(0., 0.), 100., Style::Fill));
+    assert_area(&corner, PI * 100. * 100. / 4.);
+
+    let right = drawn(|fb| {
+        let rect = [
+            Point::new(WIDTH as f32 - 10., 100.),
+            Point::new(WIDTH as f32 + 50., 100.),
+            Point::new(WIDTH as f32 + 50., 120.),
+            Point::new(WIDTH as f32 - 10., 120.),
+        ];
+        fb.polygon(Paint::Darken, &rect, FillRule::EvenOdd)
+    });
+    assert_area(&right, 10. * 20.);
+
+    let bottom = drawn(|fb| {
+        let (from, to) = (
+            Point::new(-50., 500.),
+            Point::new(2000., HEIGHT as f32 + 300.),
+        );
+        fb.line(Paint::Darken, from, to, 20.);
+        fb.arc(
+            Paint::Darken,
+            Point::new(-1e4, -1e4),
+            10.,
+            0.,
+            1.,
+            Style::Fill,
+        );
+        fb.polygon(
+            Paint::Darken,
+            &star(Point::new(5e3, 5e3), 100.),
+            FillRule::NonZero,
+        );
+    });
+    assert_eq!(bottom.get(0, 0), WHITE);
+    assert!(ink(&bottom) > 0.);
+}
+
+#[test]
+fn lightening_is_the_inverse_of_darkening() {
+    let draw = |fb: &mut Framebuffer, paint| {
+        let center = Point::new(300., 200.);
+        fb.circle(paint, center, 80.5, Style::Stroke { width: 2.5 });
+        fb.arc(paint, center, 60., 1., 2., Style::Fill);
+        fb.line(paint, Point::new(20., 30.), Point::new(900., 500.), 4.);
+        fb.polygon(
+            paint,
+            &star(Point::new(700., 250.), 120.),
+            FillRule::EvenOdd,
+        );
+    };
+    let darkened = drawn(|fb| draw(fb, Paint::Darken));
+    let lightened = drawn(|fb| {
+        let screen = Rect {
+            x: 0,
+            y: 0,
+            w: WIDTH,
+            h: HEIGHT,
+        };
+        fb.rect(Paint::Darken, screen);
+        draw(fb, Paint::Lighten)
+    });
+    for (dark, light) in darkened.pixels().iter().zip(lightened.pixels()) {
+        assert_eq!(WHITE - dark, *light);
+    }
+}
@@ -0,0 +1,138 @@
+//! Scripts run through the `paperslave-sim` binary.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+use paperslave_core::config::VBUS_SENSE_GPIO;
+
+/// Run `script` from stdin with a fresh output directory named after `name`.
+fn simulate(name: &str, script: &str) -> (Output, PathBuf) {
+    let output_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&output_dir);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_paperslave-sim"))
+        .arg(&output_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(script.as_bytes())
+        .unwrap();
+    (child.wait_with_output().unwrap(), output_dir)
+}
+
+fn frames(output_dir: &Path) -> Vec<String> {
+    let mut frames: Vec<String> = fs::read_dir(output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    frames.sort();
+    frames
+}
+
+#[test]
+fn pulse_boots_draw_the_clock_face() {
+    let (output, output_dir) = simulate(
+        "pulse_boots",
+        "time 2026-10-17T12:34\n# the first pulse shows the time as set\nboot\nboot\nboot\n",
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("boot at 2026-10-17T12:34"));
+    assert!(stdout.contains("boot at 2026-10-17T12:36"));
+    assert_eq!(
+        frames(&output_dir),
+        ["0001-full.png", "0002-full.png", "0003-full.png"]
+    );
+}
+
+#[test]
+fn buttons_adjust_and_store_the_time() {
+    let (output, output_dir) = simulate(
+        "adjust",
+        "time 2026-10-17T12:34\nstart\npress next\npress next\npress next\npress +\n\
+         press next 1000\npress +\n",
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("stored 2026-10-17T13:34"), "{stdout}");
+    let frames = frames(&output_dir);
+    assert_eq!(frames[0], "0001-full.png");
+    assert!(frames[1..]
+        .iter()
+        .all(|frame| frame.ends_with("-difference.png")));
+}
+
+#[test]
+fn start_is_a_plain_power_on() {
+    let (output, _) = simulate("power_on", "time 2026-10-17T12:34\nboot\nstart\n");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    /* Without VBUS sensing, plugging in looks like a pulse to the board. */
+    let shown = match VBUS_SENSE_GPIO {
+        None => "boot at 2026-10-17T12:35",
+        Some(_) => "boot at 2026-10-17T12:34",
+    };
+    let boots: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("boot at"))
+        .collect();
+    assert_eq!(boots, ["boot at 2026-10-17T12:34", shown]);
+}
+
+#[test]
+fn buttons_need_a_running_board() {
+    let (output, output_dir) = simulate("not_running", "press +\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("line 1: the board is not running"));
+    assert!(frames(&output_dir).is_empty());
+}
+
+#[test]
+fn catch_up_burst_holds_back_drawing() {
+    let (output, output_dir) = simulate(
+        "burst",
+        "time 2026-10-17T12:34\nboot\nboot short\nboot short\nboot short\nboot short\nboot\nboot\n",
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert_eq!(stdout.matches("power gone before drawing").count(), 4);
+    /* The fourth short boot catches up as well, but loses power before it would draw. */
+    assert_eq!(stdout.matches("catching up").count(), 1, "{stdout}");
+    assert!(stdout.contains("boot at 2026-10-17T12:40"));
+    assert_eq!(
+        frames(&output_dir),
+        ["0001-full.png", "0002-full.png", "0003-full.png"]
+    );
+}
+
+#[test]
+fn missed_pulse_is_added_from_the_polarity() {
+    let (output, _) = simulate(
+        "polarity",
+        "time 2026-10-17T12:34\nboot high\nboot low\n# the high pulse for 12:36 never came\nboot low\n",
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert_eq!(stdout.matches("corrected a missed pulse").count(), 1);
+    assert!(stdout.contains("boot at 2026-10-17T12:37"), "{stdout}");
+}
+
+#[test]
+fn unknown_boot_option() {
+    let (output, _) = simulate("boot_option", "boot long\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("line 1: unknown option long"));
+}
@@ -0,0 +1,272 @@
+//! Screens drawn by the firmware, compared against the golden images in `tests/snapshots`.
+//!
+//! After an intended layout change, rerun with `UPDATE_SNAPSHOTS=1` to rewrite the golden images
+//! and look over the result before committing them. On a mismatch, what was drawn is written next
+//! to the test binaries, under `target/<target>/tmp/snapshots`.
+
+use std::{
+    env,
+    f32::consts::TAU,
+    fs,
+    path::{Path, PathBuf},
+};
+
+mod common;
+#[path = "../src/image.rs"]
+mod image;
+
+use common::at;
+use image::{read_png, write_png};
+use paperslave_core::{
+    adjust_model::{AdjustField, AdjustModel, Button, Event, Settings},
+    adjust_view::draw_adjust,
+    face::{draw_clock_face, CORRECTED_BADGE, REPAIRED_BADGE},
+    fb::{self, Framebuffer, Paint, HEIGHT, WIDTH},
+    prepared::{DrawMode, PreparedFramebuffer},
+    profile::BootTiming,
+    pulse::PowerMode,
+    shapes::{FillRule, Point, Style},
+};
+
+/// Pixels may differ this much without counting as different, for rounding in the rasterizer.
+const LEVEL_TOLERANCE: u8 = 32;
+/// Number of differing pixels allowed, for glyph edges that land on the other side of a pixel.
+const PIXEL_TOLERANCE: usize = 100;
+
+fn adjusting(datetime: &str) -> AdjustModel {
+    let settings = Settings {
+        power_mode: PowerMode::PulsePowered,
+        timeout_ms: 60_000,
+        save_on_timeout: false,
+    };
+    AdjustModel::new(at(datetime), false, settings, None, Vec::new())
+}
+
+fn render(draw: impl FnOnce(&mut Framebuffer)) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new();
+    draw(&mut framebuffer);
+    framebuffer
+}
+
+/// Compare `framebuffer` with the golden image `name`, or replace the golden image when updating.
+fn assert_snapshot(name: &str, framebuffer: &Framebuffer) {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.png"));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        write_png(&golden, framebuffer).unwrap();
+        return;
+    }
+    assert!(
+        golden.exists(),
+        "no golden image {}, run with UPDATE_SNAPSHOTS=1 to create it",
+        golden.display()
+    );
+    let expected = read_png(&golden).unwrap();
+    let differing = expected
+        .iter()
+        .zip(framebuffer.pixels())
+        .filter(|(expected, actual)| expected.abs_diff(**actual) > LEVEL_TOLERANCE)
+        .count();
+    if differing > PIXEL_TOLERANCE {
+        let actual_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("snapshots");
+        fs::create_dir_all(&actual_dir).unwrap();
+        let actual = actual_dir.join(format!("{name}.png"));
+        write_png(&actual, framebuffer).unwrap();
+        panic!(
+            "{differing} pixels differ from {}, drawn instead: {}",
+            golden.display(),
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn clock_face() {
+    let framebuffer = render(|fb| draw_clock_face(fb, at("2024-05-06T07:08"), &[]));
+    assert_snapshot("clock_face", &framebuffer);
+}
+
+#[test]
+fn clock_face_with_badges() {
+    let badges = [REPAIRED_BADGE, CORRECTED_BADGE];
+    let framebuffer = render(|fb| draw_clock_face(fb, at("2024-12-31T23:59"), &badges));
+    assert_snapshot("clock_face_with_badges", &framebuffer);
+}
+
+#[test]
+fn adjust_screen_for_each_field() {
+    let mut model = adjusting("2024-05-06T07:08");
+    for _ in 0..7 {
+        let name = format!("adjust_{:?}", model.field).to_lowercase();
+        assert_snapshot(&name, &render(|fb| draw_adjust(fb, &model)));
+        model.handle(Event::Press(Button::Cycle));
+    }
+    assert_eq!(model.field, AdjustField::Years);
+}
+
+#[test]
+fn adjust_screen_with_unsaved_change() {
+    let mut model = adjusting("2024-05-06T07:08");
+    model.handle(Event::Press(Button::Forward));
+    assert!(model.changed);
+    assert_snapshot("adjust_changed", &render(|fb| draw_adjust(fb, &model)));
+}
+
+#[test]
+fn adjust_screen_with_history_and_boot_timing() {
+    let mut model = adjusting("2024-05-06T07:08");
+    model.history = vec![
+        (at("2024-05-01T12:00"), at("2024-05-01T12:03")),
+        (at("2024-05-04T06:30"), at("2024-05-04T06:29")),
+    ];
+    model.worst_boot = Some(BootTiming {
+        counter_us: 40_000,
+        render_us: 210_000,
+        prepare_us: 30_000,
+        quick_clear_us: 420_000,
+        draw_us: 650_000,
+        total_us: 1_480_000,
+    });
+    assert_snapshot("adjust_history", &render(|fb| draw_adjust(fb, &model)));
+}
+
+#[test]
+fn lines_circles_and_arcs() {
+    let framebuffer = render(|fb| {
+        /* A fan of lines of growing width, at angles that don't line up with the pixels. */
+        let hub = Point::new(40.5, 500.);
+        for i in 0..12 {
+            let angle = -TAU / 4. + i as f32 * TAU / 48.;
+            let end = Point::new(hub.x + 420. * angle.cos(), hub.y + 420. * angle.sin());
+            fb.line(Paint::Darken, hub, end, 0.5 + i as f32);
+        }
+
+        let center = Point::new(650.3, 170.6);
+        fb.circle(Paint::Darken, center, 140., Style::Stroke { width: 6. });
+        fb.circle(Paint::Darken, center, 110., Style::Fill);
+        fb.arc(
+            Paint::Lighten,
+            center,
+            90.,
+            -TAU / 4.,
+            TAU / 3.,
+            Style::Fill,
+        );
+        fb.arc(
+            Paint::Lighten,
+            center,
+            60.,
+            TAU / 8.,
+            5. * TAU / 8.,
+            Style::Stroke { width: 12. },
+        );
+        fb.arc(
+            Paint::Darken,
+            Point::new(650., 420.),
+            80.,
+            TAU / 2.,
+            TAU / 2.,
+            Style::Stroke { width: 2.5 },
+        );
+
+        /* Cut off by the edges. */
+        fb.circle(
+            Paint::Darken,
+            Point::new(WIDTH as f32, HEIGHT as f32),
+            60.,
+            Style::Fill,
+        );
+        fb.line(
+            Paint::Darken,
+            Point::new(WIDTH as f32 - 40., -20.),
+            Point::new(WIDTH as f32 + 20., 80.),
+            8.,
+        );
+    });
+    assert_snapshot("shapes_lines_circles_arcs", &framebuffer);
+}
+
+#[test]
+fn polygons_by_fill_rule() {
+    let star = |center: Point, radius: f32, points: usize, step: usize| -> Vec<Point> {
+        (0..points)
+            .map(|i| {
+                let angle = -TAU / 4. + (i * step) as f32 * TAU / points as f32;
+                Point::new(
+                    center.x + radius * angle.cos(),
+                    center.y + radius * angle.sin(),
+                )
+            })
+            .collect()
+    };
+    let framebuffer = render(|fb| {
+        for (x, rule) in [(240., FillRule::EvenOdd), (720., FillRule::NonZero)] {
+            fb.polygon(Paint::Darken, &star(Point::new(x, 190.), 170., 5, 2), rule);
+            fb.polygon(
+                Paint::Darken,
+                &star(Point::new(x - 110., 450.), 80., 7, 3),
+                rule,
+            );
+            /* Two squares wound the same way, one inside the other. */
+            let square = |size: f32| {
+                [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.), (-1., -1.)]
+                    .map(|(dx, dy)| Point::new(x + 90. + dx * size, 450. + dy * size))
+            };
+            let mut nested = square(70.).to_vec();
+            nested.extend(square(35.));
+            fb.polygon(Paint::Darken, &nested, rule);
+        }
+        /* Cut off by the left edge. */
+        fb.polygon(
+            Paint::Darken,
+            &[
+                Point::new(-50., 10.),
+                Point::new(30.3, 60.),
+                Point::new(-50., 110.),
+            ],
+            FillRule::NonZero,
+        );
+    });
+    assert_snapshot("shapes_polygons", &framebuffer);
+}
+
+#[test]
+fn binary_update_is_what_adjust_mode_shows() {
+    let framebuffer = render(|fb| draw_clock_face(fb, at("2024-05-06T07:08"), &[CORRECTED_BADGE]));
+    let prepared = PreparedFramebuffer::prepare(&framebuffer, DrawMode::DirectUpdateBinary);
+    let decoded = prepared.decode();
+    assert!(decoded
+        .pixels()
+        .iter()
+        .all(|&pixel| pixel == fb::BLACK || pixel == fb::WHITE));
+    assert_snapshot("clock_face_binary", &decoded);
+
+    /* A difference update ends up showing the same. */
+    let difference = PreparedFramebuffer::prepare_difference(
+        &Framebuffer::new(),
+        &framebuffer,
+        DrawMode::DirectUpdateBinary,
+    );
+    assert!(difference.decode().pixels() == decoded.pixels());
+}
+
+#[test]
+fn gray_update_keeps_sixteen_levels() {
+    let framebuffer = render(|fb| draw_clock_face(fb, at("2024-05-06T07:08"), &[]));
+    let decoded = PreparedFramebuffer::prepare(&framebuffer, DrawMode::NonFlashing).decode();
+    for (original, decoded) in framebuffer.pixels().iter().zip(decoded.pixels()) {
+        assert_eq!(original >> 4, decoded >> 4);
+    }
+}
+
+#[test]
+fn pgm_has_header_and_pixels() {
+    let framebuffer = render(|fb| draw_clock_face(fb, at("2024-05-06T07:08"), &[]));
+    let mut pgm = Vec::new();
+    framebuffer.write_pgm(&mut pgm).unwrap();
+    let header = format!("P5\n{WIDTH} {HEIGHT}\n255\n");
+    assert_eq!(&pgm[..header.len()], header.as_bytes());
+    assert!(&pgm[header.len()..] == framebuffer.pixels());
+}
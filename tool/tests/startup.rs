@@ -0,0 +1,251 @@
+//! The boot sequence shared by the firmware and the simulator, over the partitions it logs to.
+
+use paperslave_core::{
+    config::{BURST_MAX_HELD_BOOTS, BURST_MIN_RAPID_BOOTS, BURST_SETTLE_MS},
+    counter::{read_counter, set_counter, Corruption},
+    event_log::{read_events, Event},
+    face::{CORRECTED_BADGE, REPAIRED_BADGE},
+    flash::{FlashStorage, SimulatedFlash},
+    profile::{read_timings, BootTiming},
+    pulse::PowerMode,
+    reset::ResetReason,
+    startup::{Boot, Counted},
+};
+
+/// How long a normal pulse keeps the board powered.
+const PULSE_MS: u64 = 2000;
+/// How long the quick clear and the draw take.
+const DRAW_MS: u64 = 1000;
+/// A catch-up pulse, over before anything is drawn.
+const SHORT_PULSE_MS: u64 = 300;
+
+struct Board {
+    counter: SimulatedFlash,
+    log: SimulatedFlash,
+    profile: SimulatedFlash,
+}
+
+impl Board {
+    fn new(value: u32) -> Board {
+        let mut counter = SimulatedFlash::new(4);
+        set_counter(&mut counter, value).unwrap();
+        Board {
+            counter,
+            log: SimulatedFlash::new(4),
+            profile: SimulatedFlash::new(2),
+        }
+    }
+
+    fn classify(&self, reset_reason: ResetReason, usb_powered: Option<bool>) -> Boot {
+        Boot::classify(
+            &self.log,
+            reset_reason,
+            usb_powered,
+            PowerMode::PulsePowered,
+            BURST_MIN_RAPID_BOOTS,
+            BURST_MAX_HELD_BOOTS,
+        )
+        .unwrap()
+    }
+
+    /// A power-on from a pulse with line level `high` that keeps the board powered for
+    /// `length_ms`, which draws if the wait for a burst and the draw fit in it. Returns whether it
+    /// drew.
+    fn pulse(&mut self, high: bool, length_ms: u64) -> (Boot, Counted, bool) {
+        let boot = self.classify(ResetReason::PowerOn, Some(false));
+        let counted = boot
+            .count(&mut self.counter, &mut self.log, Some(high))
+            .unwrap();
+        let settle_ms = if boot.catching_up { BURST_SETTLE_MS } else { 0 };
+        let drawn = settle_ms + DRAW_MS <= length_ms;
+        if drawn {
+            let timing = BootTiming {
+                total_us: ((settle_ms + DRAW_MS) * 1000) as u32,
+                ..BootTiming::default()
+            };
+            boot.drawn(&mut self.log, &mut self.profile, Some(&timing))
+                .unwrap();
+        }
+        (boot, counted, drawn)
+    }
+}
+
+#[test]
+fn only_a_power_on_without_usb_is_a_pulse_boot() {
+    let board = Board::new(100);
+    assert!(board.classify(ResetReason::PowerOn, Some(false)).pulse_boot);
+    assert!(board.classify(ResetReason::PowerOn, None).pulse_boot);
+    assert!(!board.classify(ResetReason::PowerOn, Some(true)).pulse_boot);
+    assert!(
+        !board
+            .classify(ResetReason::DeepSleep, Some(false))
+            .pulse_boot
+    );
+    assert!(!board.classify(ResetReason::Brownout, None).pulse_boot);
+
+    let continuous = Boot::classify(
+        &board.log,
+        ResetReason::PowerOn,
+        Some(false),
+        PowerMode::Continuous,
+        BURST_MIN_RAPID_BOOTS,
+        BURST_MAX_HELD_BOOTS,
+    )
+    .unwrap();
+    assert!(!continuous.pulse_boot);
+}
+
+#[test]
+fn pulse_boot_counts_and_logs() {
+    let mut board = Board::new(100);
+    let (boot, counted, _) = board.pulse(true, PULSE_MS);
+    assert_eq!((boot.pulse_boot, boot.catching_up), (true, false));
+    assert_eq!(
+        counted,
+        Counted {
+            value: 100,
+            repair: None,
+            corrected: false,
+        }
+    );
+    assert!(counted.badges().is_empty());
+    assert_eq!(
+        read_events(&board.log).unwrap(),
+        [
+            Event::Boot {
+                reset_reason: ResetReason::PowerOn,
+                counter: 100,
+                pulse_boot: true,
+                repaired: false,
+                corrected: false,
+                catching_up: false,
+            },
+            Event::Drawn {
+                total_us: 1_000_000
+            },
+        ]
+    );
+    assert_eq!(read_timings(&board.profile).unwrap().len(), 1);
+    assert_eq!(board.pulse(false, PULSE_MS).1.value, 101);
+}
+
+#[test]
+fn other_boots_only_read_the_counter() {
+    let mut board = Board::new(100);
+    let boot = board.classify(ResetReason::DeepSleep, Some(false));
+    let counted = boot
+        .count(&mut board.counter, &mut board.log, None)
+        .unwrap();
+    assert_eq!(counted.value, 100);
+    boot.drawn(&mut board.log, &mut board.profile, None)
+        .unwrap();
+    assert_eq!(read_counter(&mut board.counter).unwrap(), 100);
+    assert!(read_timings(&board.profile).unwrap().is_empty());
+    assert_eq!(
+        read_events(&board.log).unwrap().last(),
+        Some(&Event::Drawn { total_us: 0 })
+    );
+}
+
+#[test]
+fn badges_show_a_repair_and_a_correction() {
+    let mut board = Board::new(100);
+    board.pulse(true, PULSE_MS);
+    /* A stray cleared bit past the offset. */
+    board.counter.write(64 + 100, &[0x7F]).unwrap();
+    /* The line should be low for 101. */
+    let (_, counted, _) = board.pulse(true, PULSE_MS);
+    assert_eq!(counted.repair, Some(Corruption::StrayBits { index: 100 }));
+    assert!(counted.corrected);
+    assert_eq!(counted.value, 102);
+    assert_eq!(counted.badges(), [REPAIRED_BADGE, CORRECTED_BADGE]);
+    assert!(matches!(
+        read_events(&board.log).unwrap()[2],
+        Event::Boot {
+            counter: 102,
+            repaired: true,
+            corrected: true,
+            ..
+        }
+    ));
+}
+
+/// Pulse boots of `lengths_ms` with alternating line levels, starting high. Returns whether each
+/// caught up and drew.
+fn pulses(board: &mut Board, lengths_ms: &[u64]) -> Vec<(bool, bool)> {
+    lengths_ms
+        .iter()
+        .enumerate()
+        .map(|(i, &length_ms)| {
+            let (boot, _, drawn) = board.pulse(i % 2 == 0, length_ms);
+            (boot.catching_up, drawn)
+        })
+        .collect()
+}
+
+#[test]
+fn one_undrawn_boot_does_not_keep_the_display_from_drawing() {
+    let mut board = Board::new(100);
+    assert_eq!(
+        pulses(&mut board, &[PULSE_MS, SHORT_PULSE_MS, PULSE_MS, PULSE_MS]),
+        [(false, true), (false, false), (false, true), (false, true)]
+    );
+    assert_eq!(read_counter(&mut board.counter).unwrap(), 103);
+}
+
+#[test]
+fn burst_boots_hold_back_and_keep_their_timing() {
+    let mut board = Board::new(100);
+    let mut lengths_ms = vec![PULSE_MS];
+    lengths_ms.extend([SHORT_PULSE_MS; BURST_MIN_RAPID_BOOTS as usize + 2]);
+    lengths_ms.extend([PULSE_MS, PULSE_MS]);
+    let boots = pulses(&mut board, &lengths_ms);
+    let rapid = BURST_MIN_RAPID_BOOTS as usize;
+    assert_eq!(boots[0], (false, true));
+    assert!(boots[1..=rapid].iter().all(|&boot| boot == (false, false)));
+    assert!(boots[rapid + 1..rapid + 3]
+        .iter()
+        .all(|&boot| boot == (true, false)));
+    /* The burst has settled and this pulse is long enough to wait and draw. */
+    assert_eq!(boots[rapid + 3], (true, true));
+    assert_eq!(boots[rapid + 4], (false, true));
+    assert_eq!(
+        read_counter(&mut board.counter).unwrap(),
+        100 + lengths_ms.len() as u32 - 1
+    );
+    assert_eq!(
+        read_timings(&board.profile)
+            .unwrap()
+            .iter()
+            .map(|timing| u64::from(timing.total_us))
+            .collect::<Vec<_>>(),
+        [
+            DRAW_MS * 1000,
+            (BURST_SETTLE_MS + DRAW_MS) * 1000,
+            DRAW_MS * 1000
+        ]
+    );
+}
+
+#[test]
+fn held_back_boots_draw_anyway_after_a_while() {
+    /* Long enough to draw, but not to wait for a burst first. */
+    let length_ms = DRAW_MS + BURST_SETTLE_MS / 2;
+    let mut board = Board::new(100);
+    let mut lengths_ms = vec![SHORT_PULSE_MS; BURST_MIN_RAPID_BOOTS as usize];
+    lengths_ms.extend([length_ms; BURST_MAX_HELD_BOOTS as usize + 2]);
+    let boots = pulses(&mut board, &lengths_ms);
+    let held = &boots[BURST_MIN_RAPID_BOOTS as usize..];
+    assert!(held[..BURST_MAX_HELD_BOOTS as usize]
+        .iter()
+        .all(|&boot| boot == (true, false)));
+    assert_eq!(held[BURST_MAX_HELD_BOOTS as usize..], [(false, true); 2]);
+}
+
+#[test]
+fn settle_and_draw_fit_in_a_pulse() {
+    const {
+        assert!(BURST_MIN_RAPID_BOOTS > 1);
+        assert!(BURST_SETTLE_MS + DRAW_MS <= PULSE_MS);
+    }
+}